@@ -4,31 +4,41 @@ use std::{
     time::Duration,
 };
 
-use async_openai::types::{
-    AssistantObject, AssistantToolsRetrieval, CreateAssistantFileRequest, CreateAssistantRequest,
-    CreateFileRequest, CreateRunRequest, CreateThreadRequest, ModifyAssistantRequest, RunStatus,
-    ThreadObject,
+use async_openai::{
+    config::Config,
+    types::{
+        AssistantObject, AssistantToolsRetrieval, CreateAssistantFileRequest,
+        CreateAssistantRequest, CreateFileRequest, CreateRunRequest, CreateThreadRequest,
+        ModifyAssistantRequest, RunStatus, ThreadObject,
+    },
 };
-use console::Term;
 use derive_more::{Deref, Display, From};
 use serde::{Deserialize, Serialize};
 use tokio::time::sleep;
+use tracing::{debug, error, info, warn};
 
 use crate::{
-    ais::msg::{get_text_content, user_msg},
+    ais::{
+        msg::{copy_msg, user_msg, ThreadMsg},
+        steps::{RunProgress, RunStep},
+    },
     utils::{
-        cli::{icon_check, icon_deleted_ok, icon_err, icon_uploaded, icon_uploading},
+        cli::{icon_check, icon_deleted_ok, icon_uploaded, icon_uploading},
         files::XFile,
     },
     Result,
 };
 
-use super::OpenAIClient;
+use super::{api_call, OpenAIClient};
 
 // TODO: Define the CONSTANTS in a separate file constants.rs
 // Constants
 const DEFAULT_QUERY: &[(&str, &str)] = &[("limit", "100")];
+const MSGS_PAGE_LIMIT: &str = "100";
+const STEPS_QUERY: &[(&str, &str)] = &[("limit", "100"), ("order", "asc")];
 const POLLING_DURATION_MS: u64 = 500;
+// The run steps are listed every N polls (or when the run status changes), not on each poll
+const STEPS_POLLING_EVERY: usize = 4;
 
 pub struct CreateConfig {
     pub name: String,
@@ -39,24 +49,40 @@ pub struct CreateConfig {
 #[derive(Debug, Display, From, Deref)]
 pub struct AssistantId(String);
 
-#[derive(Debug, Display, From, Deref, Serialize, Deserialize)]
+#[derive(Debug, Clone, Display, From, Deref, Serialize, Deserialize)]
 pub struct ThreadId(String);
 
-#[derive(Debug, Display, From, Deref)]
+#[derive(Debug, Clone, Display, From, Deref)]
 pub struct FileId(String);
 
+// * Answer of a completed run
+pub struct RunOutput {
+    pub text: String,
+    pub model: String,
+    // `None` if the API did not report it
+    pub usage: Option<RunUsage>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RunUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
 // CRUD
 async fn create(openai_client: &OpenAIClient, config: CreateConfig) -> Result<AssistantId> {
     let openai_assistants = openai_client.assistants();
 
-    let assistant_object = openai_assistants
-        .create(CreateAssistantRequest {
-            name: Some(config.name),
+    let assistant_object = api_call(
+        format!("POST /assistants '{}'", config.name),
+        openai_assistants.create(CreateAssistantRequest {
+            name: Some(config.name.clone()),
             model: config.model,
             tools: Some(vec![AssistantToolsRetrieval::default().into()]),
             ..Default::default()
-        })
-        .await?;
+        }),
+    )
+    .await?;
 
     Ok(assistant_object.id.into())
 }
@@ -73,18 +99,18 @@ pub async fn load_or_create(
     if let (true, Some(assistant_id_ref)) = (recreate, assistant_id.as_ref()) {
         delete(openai_client, assistant_id_ref).await?;
         assistant_id.take();
-        println!("{} Assistant {} Deleted", icon_deleted_ok(), config.name);
+        info!("{} Assistant {} Deleted", icon_deleted_ok(), config.name);
     }
 
     // Load if exists
     if let Some(assistant_id) = assistant_id {
-        println!("{} Assistant {} Loaded", icon_check(), config.name);
+        info!("{} Assistant {} Loaded", icon_check(), config.name);
         Ok(assistant_id)
     } else {
         // Create if needed
         let assistant_name = config.name.clone();
         let assistant_id = create(openai_client, config).await?;
-        println!("{} Assistant {} Created", icon_check(), assistant_name);
+        info!("{} Assistant {} Created", icon_check(), assistant_name);
         Ok(assistant_id)
     }
 }
@@ -93,7 +119,9 @@ async fn find_by_name(openai_client: &OpenAIClient, name: &str) -> Result<Option
     let openai_assistants = openai_client.assistants();
 
     // TODO: There could be a pagination to this query
-    let assistants = openai_assistants.list(DEFAULT_QUERY).await?.data;
+    let assistants = api_call("GET /assistants", openai_assistants.list(DEFAULT_QUERY))
+        .await?
+        .data;
 
     let assistant_object = assistants.into_iter().find(|asst| {
         asst.name
@@ -115,7 +143,11 @@ pub async fn upload_instructions(
         instructions: Some(instruction_content),
         ..Default::default()
     };
-    openai_assistants.update(assistant_id, modify).await?;
+    api_call(
+        format!("POST /assistants/{assistant_id} (instructions)"),
+        openai_assistants.update(assistant_id, modify),
+    )
+    .await?;
 
     Ok(())
 }
@@ -129,17 +161,25 @@ async fn delete(openai_client: &OpenAIClient, assistant_id: &AssistantId) -> Res
         .await?
         .into_values()
     {
-        let del_res = openai_files.delete(&file_id).await;
+        let del_res = api_call(
+            format!("DELETE /files/{file_id}"),
+            openai_files.delete(&file_id),
+        )
+        .await;
         // ! NOTE: Might already be deleted
         if del_res.is_ok() {
-            println!("{} File Deleted - '{}'", icon_deleted_ok(), file_id);
+            info!("{} File Deleted - '{}'", icon_deleted_ok(), file_id);
         }
     }
 
     // ! NOTE: No need to delete the Files association with the Assistant, since when deleting the Assistant the associations will also be deleted
 
     // Delete Assistant
-    openai_assistants.delete(assistant_id).await?;
+    api_call(
+        format!("DELETE /assistants/{assistant_id}"),
+        openai_assistants.delete(assistant_id),
+    )
+    .await?;
 
     Ok(())
 }
@@ -148,112 +188,301 @@ async fn delete(openai_client: &OpenAIClient, assistant_id: &AssistantId) -> Res
 pub async fn create_thread(openai_client: &OpenAIClient) -> Result<ThreadId> {
     let openai_threads = openai_client.threads();
 
-    let res = openai_threads
-        .create(CreateThreadRequest {
+    let res = api_call(
+        "POST /threads",
+        openai_threads.create(CreateThreadRequest {
             ..Default::default()
-        })
-        .await?;
+        }),
+    )
+    .await?;
 
     Ok(res.id.into())
 }
 
+// * Creates a new thread with a copy of the messages, in order
+pub async fn create_thread_with_msgs(
+    openai_client: &OpenAIClient,
+    msgs: &[ThreadMsg],
+) -> Result<ThreadId> {
+    let thread_id = create_thread(openai_client).await?;
+
+    let openai_threads = openai_client.threads();
+    let openai_messages = openai_threads.messages(&thread_id);
+    for msg in msgs {
+        api_call(
+            format!("POST /threads/{thread_id}/messages (copy of {})", msg.id),
+            openai_messages.create(copy_msg(msg)),
+        )
+        .await?;
+    }
+
+    Ok(thread_id)
+}
+
+pub async fn delete_thread(openai_client: &OpenAIClient, thread_id: &ThreadId) -> Result<()> {
+    api_call(
+        format!("DELETE /threads/{thread_id}"),
+        openai_client.threads().delete(thread_id),
+    )
+    .await?;
+
+    Ok(())
+}
+
 pub async fn get_thread(
     openai_client: &OpenAIClient,
     thread_id: &ThreadId,
 ) -> Result<ThreadObject> {
     let openai_threads = openai_client.threads();
 
-    let thread_object = openai_threads.retrieve(thread_id).await?;
+    let thread_object = api_call(
+        format!("GET /threads/{thread_id}"),
+        openai_threads.retrieve(thread_id),
+    )
+    .await?;
 
     Ok(thread_object)
 }
 
+// * Runs the message on the thread, and returns the answer once the run is completed
+// - `on_progress` is called with the live status of the run, when it changes
 pub async fn run_thread_msg(
     openai_client: &OpenAIClient,
     assistant_id: &AssistantId,
     thread_id: &ThreadId,
     msg: &str,
-) -> Result<String> {
+    on_progress: impl Fn(&RunProgress),
+) -> Result<RunOutput> {
     let msg = user_msg(msg);
 
     // Attach message to thread
-    let _message_obj = openai_client
-        .threads()
-        .messages(thread_id)
-        .create(msg)
-        .await?;
+    let openai_threads = openai_client.threads();
+    let _message_obj = api_call(
+        format!("POST /threads/{thread_id}/messages"),
+        openai_threads.messages(thread_id).create(msg),
+    )
+    .await?;
 
     // Create a run for the thread
     let run_req = CreateRunRequest {
         assistant_id: assistant_id.to_string(),
         ..Default::default()
     };
-    let run = openai_client
-        .threads()
-        .runs(thread_id)
-        .create(run_req)
-        .await?;
+    let openai_runs = openai_threads.runs(thread_id);
+    let run = api_call(
+        format!("POST /threads/{thread_id}/runs"),
+        openai_runs.create(run_req),
+    )
+    .await?;
     let run_id = run.id;
 
     // Loop to get the result
-    let term = Term::stdout();
+    let mut last_progress = None;
+    let mut last_status = None;
+    let mut latest_step = None;
+    let mut poll_idx: usize = 0;
     loop {
-        term.write_str(">")?;
-        let run = openai_client
-            .threads()
-            .runs(thread_id)
-            .retrieve(&run_id)
-            .await?;
-        term.write_str("< ")?;
+        let run = api_call(
+            format!("GET /threads/{thread_id}/runs/{run_id}"),
+            openai_runs.retrieve(&run_id),
+        )
+        .await?;
+
+        // The latest step tells if the assistant is retrieving, calling a tool, or writing
+        let status_changed = last_status.as_ref() != Some(&run.status);
+        if run.status != RunStatus::InProgress {
+            latest_step = None;
+        } else if status_changed || poll_idx.is_multiple_of(STEPS_POLLING_EVERY) {
+            let query = [("limit", "1"), ("order", "desc")];
+            latest_step = api_call(
+                format!("GET /threads/{thread_id}/runs/{run_id}/steps"),
+                openai_runs.steps(&run_id).list(&query),
+            )
+            .await?
+            .data
+            .into_iter()
+            .next();
+        }
+        last_status = Some(run.status.clone());
+        let progress = RunProgress::new(&run.status, latest_step.as_ref());
+        if last_progress.as_ref() != Some(&progress) {
+            debug!("Run {run_id} - {progress}");
+            on_progress(&progress);
+            last_progress = Some(progress);
+        }
+
         match run.status {
             RunStatus::Completed => {
-                term.write_str("\n")?;
-                return get_first_thread_msg_content(openai_client, thread_id).await;
+                let text = get_first_thread_msg_content(openai_client, thread_id).await?;
+                let usage = get_run_usage(openai_client, thread_id, &run_id).await?;
+                return Ok(RunOutput {
+                    text,
+                    model: run.model,
+                    usage,
+                });
             }
             RunStatus::Queued | RunStatus::InProgress => (),
             other => {
-                term.write_str("\n")?;
                 return Err(format!("Error while Run: {:?}", other).into());
             }
         }
 
         sleep(Duration::from_millis(POLLING_DURATION_MS)).await;
+        poll_idx += 1;
     }
 }
 
-async fn get_first_thread_msg_content(
+// * Returns the id of the latest run of the thread, if any
+pub async fn get_last_run_id(
     openai_client: &OpenAIClient,
     thread_id: &ThreadId,
-) -> Result<String> {
-    static QUERY: [(&str, &str); 1] = [("limit", "1")];
+) -> Result<Option<String>> {
+    let query = [("limit", "1"), ("order", "desc")];
+    let openai_threads = openai_client.threads();
+    let runs = api_call(
+        format!("GET /threads/{thread_id}/runs"),
+        openai_threads.runs(thread_id).list(&query),
+    )
+    .await?;
+
+    Ok(runs.data.into_iter().next().map(|run| run.id))
+}
+
+// * Returns the steps of the run, oldest first
+pub async fn list_run_steps(
+    openai_client: &OpenAIClient,
+    thread_id: &ThreadId,
+    run_id: &str,
+) -> Result<Vec<RunStep>> {
+    let openai_threads = openai_client.threads();
+    let openai_runs = openai_threads.runs(thread_id);
+    let steps = api_call(
+        format!("GET /threads/{thread_id}/runs/{run_id}/steps"),
+        openai_runs.steps(run_id).list(STEPS_QUERY),
+    )
+    .await?;
+
+    Ok(steps.data.into_iter().map(RunStep::from).collect())
+}
+
+// * Returns the token usage of a completed run
+// ! NOTE: The `RunObject` of async-openai 0.17 has no `usage`, so the run is retrieved as raw JSON
+async fn get_run_usage(
+    openai_client: &OpenAIClient,
+    thread_id: &ThreadId,
+    run_id: &str,
+) -> Result<Option<RunUsage>> {
+    #[derive(Deserialize)]
+    struct RawRun {
+        usage: Option<RunUsage>,
+    }
+
+    let config = openai_client.config();
+    let request = reqwest::Client::new()
+        .get(config.url(&format!("/threads/{thread_id}/runs/{run_id}")))
+        .headers(config.headers())
+        .query(&config.query())
+        .send();
+    let raw_run: RawRun = api_call(
+        format!("GET /threads/{thread_id}/runs/{run_id} (usage)"),
+        request,
+    )
+    .await?
+    .error_for_status()?
+    .json()
+    .await?;
+
+    Ok(raw_run.usage)
+}
+
+// * Returns all the messages of the thread, oldest first (Pages through the whole thread)
+pub async fn list_thread_msgs(
+    openai_client: &OpenAIClient,
+    thread_id: &ThreadId,
+) -> Result<Vec<ThreadMsg>> {
+    let openai_threads = openai_client.threads();
+    let openai_messages = openai_threads.messages(thread_id);
 
-    let messages = openai_client
-        .threads()
-        .messages(thread_id)
-        .list(&QUERY)
+    let mut msgs = Vec::new();
+    let mut after: Option<String> = None;
+    loop {
+        let mut query = vec![("limit", MSGS_PAGE_LIMIT), ("order", "asc")];
+        if let Some(after) = after.as_deref() {
+            query.push(("after", after));
+        }
+
+        let page = api_call(
+            format!("GET /threads/{thread_id}/messages {query:?}"),
+            openai_messages.list(&query),
+        )
         .await?;
+        msgs.extend(page.data.into_iter().map(ThreadMsg::from));
+
+        match page.last_id {
+            Some(last_id) if page.has_more => after = Some(last_id),
+            _ => break,
+        }
+    }
+
+    Ok(msgs)
+}
+
+// * Returns a page of the thread messages, newest first
+// - `after` is the id of the oldest message of the previous page, to get the older messages
+//
+// Return `(msgs, has_more)`
+pub async fn list_thread_msgs_page(
+    openai_client: &OpenAIClient,
+    thread_id: &ThreadId,
+    limit: usize,
+    after: Option<&str>,
+) -> Result<(Vec<ThreadMsg>, bool)> {
+    let limit = limit.to_string();
+    let mut query = vec![("limit", limit.as_str()), ("order", "desc")];
+    if let Some(after) = after {
+        query.push(("after", after));
+    }
+
+    let openai_threads = openai_client.threads();
+    let page = api_call(
+        format!("GET /threads/{thread_id}/messages {query:?}"),
+        openai_threads.messages(thread_id).list(&query),
+    )
+    .await?;
+    let msgs = page.data.into_iter().map(ThreadMsg::from).collect();
+
+    Ok((msgs, page.has_more))
+}
 
-    let msg = messages
-        .data
+async fn get_first_thread_msg_content(
+    openai_client: &OpenAIClient,
+    thread_id: &ThreadId,
+) -> Result<String> {
+    let (msgs, _) = list_thread_msgs_page(openai_client, thread_id, 1, None).await?;
+
+    let msg = msgs
         .into_iter()
         .next()
         .ok_or_else(|| "No message found".to_string())?;
 
-    let text = get_text_content(msg)?;
-
-    Ok(text)
+    Ok(msg.text)
 }
 
 // Files
 // * Return the File Id by File Name Hashmap
-async fn get_files_hashmap(
+pub async fn get_files_hashmap(
     openai_client: &OpenAIClient,
     assistant_id: &AssistantId,
 ) -> Result<HashMap<String, FileId>> {
     // Get all Assistant Files (Files do NOT have .name)
     let openai_assistants = openai_client.assistants();
     let openai_assistant_files = openai_assistants.files(assistant_id);
-    let assistant_files = openai_assistant_files.list(DEFAULT_QUERY).await?.data;
+    let assistant_files = api_call(
+        format!("GET /assistants/{assistant_id}/files"),
+        openai_assistant_files.list(DEFAULT_QUERY),
+    )
+    .await?
+    .data;
     let assistant_file_ids: HashSet<String> = assistant_files
         .into_iter()
         .map(|file_obj| file_obj.id)
@@ -262,7 +491,7 @@ async fn get_files_hashmap(
     // Get all Files for Organization (Those files have .filename)
     let openai_files = openai_client.files();
     // let organization_files = openai_files.list([("purpose", "assistants")]).await?.data; // For async-openai version 0.18
-    let organization_files = openai_files.list().await?.data;
+    let organization_files = api_call("GET /files", openai_files.list()).await?.data;
 
     // Build or file_name::file_id Hashmap
     let file_id_by_name: HashMap<String, FileId> = organization_files
@@ -274,7 +503,56 @@ async fn get_files_hashmap(
     Ok(file_id_by_name)
 }
 
+// * Return the File Name by File Id Hashmap, for all the Organization Files
+pub async fn get_file_names_by_id(openai_client: &OpenAIClient) -> Result<HashMap<String, String>> {
+    let openai_files = openai_client.files();
+    let organization_files = api_call("GET /files", openai_files.list()).await?.data;
+
+    let file_name_by_id = organization_files
+        .into_iter()
+        .map(|org_file| (org_file.id, org_file.filename))
+        .collect();
+
+    Ok(file_name_by_id)
+}
+
+// * Deletes the Organization File, and its association with the Assistant
+// - The errors are only logged (e.g., the file might already be deleted)
+pub async fn delete_file(
+    openai_client: &OpenAIClient,
+    assistant_id: &AssistantId,
+    file_id: &FileId,
+    file_name: &str,
+) {
+    // Delete the Organization File
+    let openai_files = openai_client.files();
+    let del_res = api_call(
+        format!("DELETE /files/{file_id}"),
+        openai_files.delete(file_id),
+    )
+    .await;
+    if let Err(err) = del_res {
+        warn!("Cannot Delete File '{}'\n\tError: {}", file_name, err);
+    }
+
+    // Delete the Assistant File Association
+    let openai_assistant = openai_client.assistants();
+    let openai_assistants_files = openai_assistant.files(assistant_id);
+    let del_res = api_call(
+        format!("DELETE /assistants/{assistant_id}/files/{file_id}"),
+        openai_assistants_files.delete(file_id),
+    )
+    .await;
+    if let Err(err) = del_res {
+        warn!(
+            "Cannot Remove Assistant File '{}'\n\tError: {}",
+            file_name, err
+        );
+    };
+}
+
 // * Upload a file to an Assistant (Uploads first to the account, later then attaches to the Assistant)
+// - `file_id_by_name` are the files of the Assistant (see `get_files_hashmap`), updated with the upload
 // - `force` is `false`, will not upload file if already uploaded
 // - `force` is `true`, it will delete the existing file (In the Account and Assistant), and then Upload
 //
@@ -283,89 +561,66 @@ pub async fn upload_file_by_name(
     openai_client: &OpenAIClient,
     assistant_id: &AssistantId,
     file: &Path,
+    file_id_by_name: &mut HashMap<String, FileId>,
     force: bool,
 ) -> Result<(FileId, bool)> {
     let file_name = file.x_file_name();
-    let mut file_id_by_name = get_files_hashmap(openai_client, assistant_id).await?;
-
-    let file_id = file_id_by_name.remove(file_name);
 
     // If not force and file already create, return early
     if !force {
-        if let Some(file_id) = file_id {
-            return Ok((file_id, false));
+        if let Some(file_id) = file_id_by_name.get(file_name) {
+            return Ok((file_id.clone(), false));
         }
     }
 
+    let file_id = file_id_by_name.remove(file_name);
+
     // If old file_id exists, delete the file
     if let Some(file_id) = file_id {
-        // Delete the Organization File
-        let openai_files = openai_client.files();
-        if let Err(err) = openai_files.delete(&file_id).await {
-            println!(
-                "{} Cannot Delete File '{}'\n\tError: {}",
-                icon_err(),
-                file.to_string_lossy(), // Used to_string_lossy to show the Path also
-                err
-            );
-        }
-
-        // Delete the Assistant File Association
-        let openai_assistant = openai_client.assistants();
-        let openai_assistants_files = openai_assistant.files(assistant_id);
-        if let Err(err) = openai_assistants_files.delete(&file_id).await {
-            println!(
-                "{} Cannot Remove Assistant File '{}'\n\tError: {}",
-                icon_err(),
-                file.x_file_name(),
-                err
-            );
-        };
+        delete_file(openai_client, assistant_id, &file_id, file_name).await;
     }
 
     // Upload and Attach the File
-    let term = Term::stdout();
-
-    // Print Uploading
-    term.write_line(&format!(
+    debug!(
         "{} Uploading File '{}'",
         icon_uploading(),
         file.x_file_name()
-    ))?;
+    );
 
     // Upload File
     let openai_files = openai_client.files();
-    let openai_file = openai_files
-        .create(CreateFileRequest {
+    let openai_file = api_call(
+        format!("POST /files '{}'", file.x_file_name()),
+        openai_files.create(CreateFileRequest {
             file: file.into(),
             purpose: "assistants".into(),
-        })
-        .await?;
+        }),
+    )
+    .await?;
 
-    // Update Print
-    term.clear_last_lines(1)?;
-    term.write_line(&format!(
-        "{} Uploaded File '{}'",
-        icon_uploaded(),
-        file.x_file_name()
-    ))?;
+    info!("{} Uploaded File '{}'", icon_uploaded(), file.x_file_name());
 
     // Attach File to Assistant
     let openai_assistants = openai_client.assistants();
     let openai_assistant_files = openai_assistants.files(assistant_id);
-    let assistant_file_obj = openai_assistant_files
-        .create(CreateAssistantFileRequest {
+    let assistant_file_obj = api_call(
+        format!("POST /assistants/{assistant_id}/files ({})", openai_file.id),
+        openai_assistant_files.create(CreateAssistantFileRequest {
             file_id: openai_file.id.clone(),
-        })
-        .await?;
+        }),
+    )
+    .await?;
 
     // Assert Warning
     if openai_file.id != assistant_file_obj.id {
-        println!(
+        error!(
             "Critical Error: File Id do not match {} {}",
             openai_file.id, assistant_file_obj.id
         )
     }
 
-    Ok((assistant_file_obj.id.into(), true))
+    let file_id: FileId = assistant_file_obj.id.into();
+    file_id_by_name.insert(file_name.to_string(), file_id.clone());
+
+    Ok((file_id, true))
 }
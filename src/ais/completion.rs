@@ -0,0 +1,47 @@
+use async_openai::types::{
+    ChatCompletionRequestSystemMessageArgs, ChatCompletionRequestUserMessageArgs,
+    CreateChatCompletionRequestArgs,
+};
+
+use crate::Result;
+
+use super::{api_call, OpenAIClient};
+
+// Chat Completion
+// * One shot completion, outside of any Assistant and thread (e.g., for the cheap side tasks)
+pub async fn complete(
+    openai_client: &OpenAIClient,
+    model: &str,
+    system: &str,
+    user: &str,
+) -> Result<String> {
+    let request = CreateChatCompletionRequestArgs::default()
+        .model(model)
+        .messages([
+            ChatCompletionRequestSystemMessageArgs::default()
+                .content(system)
+                .build()?
+                .into(),
+            ChatCompletionRequestUserMessageArgs::default()
+                .content(user)
+                .build()?
+                .into(),
+        ])
+        .build()?;
+
+    let openai_chat = openai_client.chat();
+    let res = api_call(
+        format!("POST /chat/completions ({model})"),
+        openai_chat.create(request),
+    )
+    .await?;
+
+    let content = res
+        .choices
+        .into_iter()
+        .next()
+        .and_then(|choice| choice.message.content)
+        .ok_or_else(|| "No completion content found".to_string())?;
+
+    Ok(content)
+}
@@ -1,9 +1,22 @@
 pub mod assistant;
+pub mod completion;
 pub mod msg;
+pub mod steps;
+
+use std::{
+    fmt::{Debug, Display},
+    future::Future,
+    time::Instant,
+};
 
 use async_openai::{config::OpenAIConfig, Client};
+use tracing::{debug, enabled, Level};
+
+use crate::{logs::API_LOG_TARGET, Result};
 
-use crate::Result;
+// TODO: Define the CONSTANTS in a separate file constants.rs
+// Max chars of a response in the API logs (e.g., for the files lists)
+const API_LOG_MAX_CHARS: usize = 500;
 
 pub type OpenAIClient = Client<OpenAIConfig>;
 
@@ -11,7 +24,33 @@ pub fn new_openai_client() -> Result<OpenAIClient> {
     if dotenv::var("OPENAI_API_KEY").is_ok() {
         Ok(Client::new())
     } else {
-        println!("No OPENAI_API_KEY variable in .env. Please add it and try again.");
-        Err("No OPENAI_API_KEY in .env".into())
+        Err("No OPENAI_API_KEY variable in .env. Please add it and try again.".into())
+    }
+}
+
+// * Awaits the API call, and logs its request and response summaries (with `--log-api`)
+// - `request` is a short description of the call (e.g., `POST /files 'name'`)
+async fn api_call<T: Debug, E: Display>(
+    request: impl Display,
+    call: impl Future<Output = std::result::Result<T, E>>,
+) -> std::result::Result<T, E> {
+    debug!(target: API_LOG_TARGET, "--> {request}");
+    let start = Instant::now();
+
+    let res = call.await;
+
+    let elapsed_ms = start.elapsed().as_millis();
+    match &res {
+        // ! NOTE: Only formatted when logged, as the responses can be large (e.g., the messages lists)
+        Ok(res) if enabled!(target: API_LOG_TARGET, Level::DEBUG) => {
+            let res = format!("{res:?}");
+            let summary: String = res.chars().take(API_LOG_MAX_CHARS).collect();
+            let ellipsis = if summary.len() < res.len() { "..." } else { "" };
+            debug!(target: API_LOG_TARGET, "<-- {request} OK ({elapsed_ms} ms) {summary}{ellipsis}");
+        }
+        Ok(_) => (),
+        Err(err) => debug!(target: API_LOG_TARGET, "<-- {request} FAILED ({elapsed_ms} ms) {err}"),
     }
+
+    res
 }
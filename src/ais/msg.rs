@@ -1,6 +1,46 @@
-use async_openai::types::{CreateMessageRequest, MessageContent, MessageObject};
+use async_openai::types::{
+    CreateMessageRequest, MessageContent, MessageContentTextAnnotations, MessageObject, MessageRole,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-use crate::Result;
+// TODO: Define the CONSTANTS in a separate file constants.rs
+const COPIED_ANSWER_PREFIX: &str = "[Previous assistant answer]\n";
+// Metadata keys of the copied messages, to restore the original role and time
+const META_COPIED_ROLE: &str = "copied_role";
+const META_COPIED_CREATED_AT: &str = "copied_created_at";
+
+// Thread Message
+// * Simplified message of a thread, used to show and export the conversations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThreadMsg {
+    pub id: String,
+    pub role: String,
+    // Unix timestamp (in seconds)
+    pub created_at: i64,
+    pub text: String,
+    pub citations: Vec<Citation>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Citation {
+    // The text of the citation in the message (e.g., `【7†source】`)
+    pub marker: String,
+    pub file_id: String,
+    // Resolved later, the file may not exist anymore
+    pub file_name: Option<String>,
+    pub quote: Option<String>,
+}
+
+impl ThreadMsg {
+    pub fn role_title(&self) -> &str {
+        match self.role.as_str() {
+            "user" => "User",
+            "assistant" => "Assistant",
+            other => other,
+        }
+    }
+}
 
 // Message Constructors
 pub fn user_msg(content: impl Into<String>) -> CreateMessageRequest {
@@ -11,20 +51,93 @@ pub fn user_msg(content: impl Into<String>) -> CreateMessageRequest {
     }
 }
 
-// Content Extractor
-pub fn get_text_content(msg: MessageObject) -> Result<String> {
-    // Get first content item
-    let msg_content = msg
-        .content
-        .into_iter()
-        .next()
-        .ok_or_else(|| "No message content found".to_string())?;
-
-    // Get the text
-    let txt = match msg_content {
-        MessageContent::Text(text) => text.text.value,
-        MessageContent::ImageFile(_) => return Err("Message image not supported yet".into()),
+// * Message to copy a thread message into another thread
+// ! NOTE: The Assistants API (v1) only accepts `user` messages when adding messages,
+//         so the assistant answers are copied as user messages, marked as previous answers.
+//         The original role and time are kept in the metadata, and restored by `ThreadMsg::from`
+pub fn copy_msg(msg: &ThreadMsg) -> CreateMessageRequest {
+    let mut copy = if msg.role == "assistant" {
+        user_msg(format!("{COPIED_ANSWER_PREFIX}{}", msg.text))
+    } else {
+        user_msg(&msg.text)
     };
 
-    Ok(txt)
+    copy.metadata = Some(
+        [
+            (META_COPIED_ROLE.to_string(), Value::from(msg.role.as_str())),
+            (
+                META_COPIED_CREATED_AT.to_string(),
+                Value::from(msg.created_at.to_string()),
+            ),
+        ]
+        .into(),
+    );
+
+    copy
+}
+
+// Froms
+impl From<MessageObject> for ThreadMsg {
+    fn from(msg: MessageObject) -> Self {
+        let role = match msg.role {
+            MessageRole::User => "user",
+            MessageRole::Assistant => "assistant",
+        };
+
+        let mut texts = Vec::new();
+        let mut citations = Vec::new();
+        for content in msg.content {
+            match content {
+                MessageContent::Text(text) => {
+                    texts.push(text.text.value);
+                    citations.extend(text.text.annotations.into_iter().map(Citation::from));
+                }
+                MessageContent::ImageFile(image) => {
+                    texts.push(format!("[Image: {}]", image.image_file.file_id));
+                }
+            }
+        }
+
+        let mut thread_msg = Self {
+            id: msg.id,
+            role: role.to_string(),
+            created_at: msg.created_at.into(),
+            text: texts.join("\n\n"),
+            citations,
+        };
+
+        // Restore the original role and time of the copied messages
+        let metadata = msg.metadata.unwrap_or_default();
+        let meta_str = |key: &str| metadata.get(key).and_then(Value::as_str);
+        if meta_str(META_COPIED_ROLE) == Some("assistant") {
+            thread_msg.role = "assistant".to_string();
+            if let Some(text) = thread_msg.text.strip_prefix(COPIED_ANSWER_PREFIX) {
+                thread_msg.text = text.to_string();
+            }
+        }
+        if let Some(created_at) = meta_str(META_COPIED_CREATED_AT).and_then(|v| v.parse().ok()) {
+            thread_msg.created_at = created_at;
+        }
+
+        thread_msg
+    }
+}
+
+impl From<MessageContentTextAnnotations> for Citation {
+    fn from(annotation: MessageContentTextAnnotations) -> Self {
+        match annotation {
+            MessageContentTextAnnotations::FileCitation(citation) => Self {
+                marker: citation.text,
+                file_id: citation.file_citation.file_id,
+                file_name: None,
+                quote: Some(citation.file_citation.quote).filter(|quote| !quote.is_empty()),
+            },
+            MessageContentTextAnnotations::FilePath(file_path) => Self {
+                marker: file_path.text,
+                file_id: file_path.file_path.file_id,
+                file_name: None,
+                quote: None,
+            },
+        }
+    }
 }
@@ -0,0 +1,159 @@
+use std::fmt;
+
+use async_openai::types::{
+    CodeInterpreterOutput, RunStatus, RunStepDetailsToolCalls, RunStepObject, StepDetails,
+};
+
+// Run Progress
+// * Live status of a run, while it is polled
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunProgress {
+    Queued,
+    InProgress,
+    Retrieving,
+    CallingTool(String),
+    Writing,
+}
+
+impl RunProgress {
+    // * Returns the progress of the run, from its status and its latest step (if any)
+    pub fn new(status: &RunStatus, latest_step: Option<&RunStepObject>) -> Self {
+        if *status == RunStatus::Queued {
+            return Self::Queued;
+        }
+
+        let Some(step) = latest_step.filter(|step| step.status == RunStatus::InProgress) else {
+            return Self::InProgress;
+        };
+        match &step.step_details {
+            StepDetails::MessageCreation(_) => Self::Writing,
+            StepDetails::ToolCalls(details) => match details.tool_calls.last() {
+                Some(RunStepDetailsToolCalls::Retrieval(_)) => Self::Retrieving,
+                Some(tool_call) => Self::CallingTool(tool_name(tool_call)),
+                None => Self::InProgress,
+            },
+        }
+    }
+}
+
+impl fmt::Display for RunProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Queued => write!(f, "Queued"),
+            Self::InProgress => write!(f, "In progress"),
+            Self::Retrieving => write!(f, "Retrieving from the files"),
+            Self::CallingTool(name) => write!(f, "Calling the tool '{name}'"),
+            Self::Writing => write!(f, "Writing the answer"),
+        }
+    }
+}
+
+// Run Steps
+// * Simplified step of a run, used to inspect how an answer was made
+#[derive(Debug, Clone)]
+pub struct RunStep {
+    pub id: String,
+    pub status: String,
+    // Unix timestamps (in seconds)
+    pub created_at: i64,
+    // `None` while in progress
+    pub ended_at: Option<i64>,
+    pub details: RunStepDetails,
+}
+
+#[derive(Debug, Clone)]
+pub enum RunStepDetails {
+    MessageCreation { message_id: String },
+    ToolCalls(Vec<ToolCall>),
+}
+
+#[derive(Debug, Clone)]
+pub struct ToolCall {
+    pub tool: String,
+    // `None` when the API does not report it (e.g., for the retrieval)
+    pub input: Option<String>,
+    pub output: Option<String>,
+}
+
+impl RunStep {
+    pub fn duration_secs(&self) -> Option<i64> {
+        self.ended_at.map(|ended_at| ended_at - self.created_at)
+    }
+}
+
+// Froms
+impl From<RunStepObject> for RunStep {
+    fn from(step: RunStepObject) -> Self {
+        let ended_at = step
+            .completed_at
+            .or(step.failed_at)
+            .or(step.cancelled_at)
+            .or(step.expired_at)
+            .map(i64::from);
+
+        let details = match step.step_details {
+            StepDetails::MessageCreation(details) => RunStepDetails::MessageCreation {
+                message_id: details.message_creation.message_id,
+            },
+            StepDetails::ToolCalls(details) => RunStepDetails::ToolCalls(
+                details.tool_calls.into_iter().map(ToolCall::from).collect(),
+            ),
+        };
+
+        Self {
+            id: step.id,
+            // The API name of the status (e.g., `in_progress`)
+            status: serde_json::to_value(&step.status)
+                .ok()
+                .and_then(|status| status.as_str().map(str::to_string))
+                .unwrap_or_default(),
+            created_at: step.created_at.into(),
+            ended_at,
+            details,
+        }
+    }
+}
+
+impl From<RunStepDetailsToolCalls> for ToolCall {
+    fn from(tool_call: RunStepDetailsToolCalls) -> Self {
+        let tool = tool_name(&tool_call);
+        match tool_call {
+            RunStepDetailsToolCalls::Code(code) => {
+                let logs: Vec<String> = code
+                    .code_interpreter
+                    .outputs
+                    .into_iter()
+                    .map(|output| match output {
+                        CodeInterpreterOutput::Log(log) => log.logs,
+                        CodeInterpreterOutput::Image(image) => {
+                            format!("[Image: {}]", image.image.file_id)
+                        }
+                    })
+                    .collect();
+                Self {
+                    tool,
+                    input: Some(code.code_interpreter.input),
+                    output: Some(logs.join("\n")).filter(|logs| !logs.is_empty()),
+                }
+            }
+            RunStepDetailsToolCalls::Retrieval(_) => Self {
+                tool,
+                input: None,
+                output: None,
+            },
+            RunStepDetailsToolCalls::Function(function) => Self {
+                tool,
+                input: Some(function.function.arguments),
+                output: function.function.output,
+            },
+        }
+    }
+}
+
+fn tool_name(tool_call: &RunStepDetailsToolCalls) -> String {
+    match tool_call {
+        RunStepDetailsToolCalls::Code(_) => "code_interpreter".to_string(),
+        RunStepDetailsToolCalls::Retrieval(_) => "retrieval".to_string(),
+        RunStepDetailsToolCalls::Function(function) => function.function.name.clone(),
+    }
+}
@@ -0,0 +1,70 @@
+use std::path::PathBuf;
+
+use clap::{ArgAction, Parser, Subcommand};
+
+use crate::{export::ExportFormat, logs::LogOptions};
+
+// Command Line Arguments
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Args {
+    /// Print the answers as raw text, without the Markdown rendering (e.g., for copying)
+    #[arg(long)]
+    pub plain: bool,
+
+    /// Chat in a full-screen terminal UI instead of the line prompt
+    #[arg(long)]
+    pub tui: bool,
+
+    /// Re-sync the bundles and instructions in the background when their files change
+    #[arg(long)]
+    pub watch: bool,
+
+    /// Name of the conversation to use (created if it does not exist)
+    #[arg(long, global = true)]
+    pub conversation: Option<String>,
+
+    /// Show more progress output (`-vv` to also show the API calls)
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    pub verbose: u8,
+
+    /// Only show the warnings and errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Log the API request and response summaries (secrets redacted) in `.buddy/logs/`
+    #[arg(long, global = true)]
+    pub log_api: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Export the current conversation to a file
+    Export {
+        format: ExportFormat,
+        /// Defaults to `{buddy}-{thread_id}.{ext}` in the current dir
+        path: Option<PathBuf>,
+    },
+    /// Show the token usage and costs, by day and by buddy
+    Usage,
+    /// Watch the bundle sources, the instructions and buddy.toml, and re-sync what changes
+    Watch,
+}
+
+impl Args {
+    pub fn log_options(&self) -> LogOptions {
+        let verbosity = if self.quiet {
+            -1
+        } else {
+            self.verbose.min(i8::MAX as u8) as i8
+        };
+
+        LogOptions {
+            verbosity,
+            log_api: self.log_api,
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use crate::{utils::files::is_listed_file, Result};
+
+use super::{config::Config, BUDDY_TOML};
+
+// * What a set of changed paths affects, for the watch mode
+#[derive(Debug, Default)]
+pub struct WatchChanges {
+    // `buddy.toml` changed, the buddy must be reloaded
+    pub config: bool,
+    pub instructions: bool,
+    // Names of the bundles with changed sources
+    pub bundles: Vec<String>,
+}
+
+impl WatchChanges {
+    // - The changes inside `data_dir` are ignored (e.g., the bundle files written by the sync)
+    pub(super) fn new(
+        dir: &Path,
+        data_dir: &Path,
+        config: &Config,
+        changed: &HashSet<PathBuf>,
+    ) -> Result<Self> {
+        let data_dir = canonical(data_dir);
+        let changed: Vec<&PathBuf> = changed
+            .iter()
+            .filter(|path| !path.starts_with(&data_dir))
+            .collect();
+
+        let config_file = canonical(&dir.join(BUDDY_TOML));
+        let instructions_file = canonical(&dir.join(&config.instructions_file));
+
+        let mut changes = Self {
+            config: changed.iter().any(|path| **path == config_file),
+            instructions: changed.iter().any(|path| **path == instructions_file),
+            bundles: Vec::new(),
+        };
+
+        for bundle in config.file_bundles.iter() {
+            let src_dir = canonical(&dir.join(&bundle.src_dir));
+            let glob_filter = bundle.glob_filter()?;
+
+            // Same files as the bundle lists (the base excludes and the ignore files are applied)
+            let mut is_affected = false;
+            for path in changed.iter() {
+                if is_listed_file(&src_dir, path, &glob_filter, bundle.respect_ignore_files)? {
+                    is_affected = true;
+                    break;
+                }
+            }
+            if is_affected {
+                changes.bundles.push(bundle.bundle_name.clone());
+            }
+        }
+
+        Ok(changes)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.config && !self.instructions && self.bundles.is_empty()
+    }
+}
+
+// * Returns the absolute path, as reported by the file watcher (or the path itself if it does not exist)
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
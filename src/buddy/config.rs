@@ -1,21 +1,176 @@
+use std::{collections::HashMap, path::Path};
+
+use encoding_rs::{Encoding, UTF_8};
 use serde::Deserialize;
 
-use crate::ais::assistant;
+use crate::{
+    ais::assistant,
+    usage::{Budget, ModelPrice},
+    utils::{
+        bundle::{BundleFormat, BundleOptions},
+        files::GlobFilter,
+    },
+    Result,
+};
 
 #[derive(Debug, Deserialize)]
 pub(super) struct Config {
     pub name: String,
-    model: String,
+    pub model: String,
     pub instructions_file: String,
+    // Number of the last exchanges (user and assistant messages) to show when the conversation is loaded
+    #[serde(default)]
+    pub history_on_start: usize,
+    // Cheap model used for the side tasks (e.g., conversation titles)
+    #[serde(default = "default_title_model")]
+    pub title_model: String,
+    // Price by model name (or model name prefix), to compute the cost of the runs
+    #[serde(default)]
+    pub prices: HashMap<String, ModelPrice>,
+    // Spending caps checked before each run
+    pub budget: Option<Budget>,
     pub file_bundles: Vec<FileBundle>,
 }
 
 #[derive(Debug, Deserialize)]
 pub(super) struct FileBundle {
     pub bundle_name: String,
+    // Purpose of the bundle, shown in its table of contents (e.g., "The Rust sources of the CLI")
+    pub description: Option<String>,
+    // Relative to the buddy dir (the buddy dir itself by default)
+    #[serde(default = "default_src_dir")]
     pub src_dir: String,
+    // Extension of the bundle file (not used by the `per_file` mode)
+    #[serde(default)]
     pub dst_ext: String,
+    // `bundle` joins the files in one bundle file, `per_file` uploads each file under its own name
+    #[serde(default)]
+    pub mode: BundleMode,
+    // Where the files come from (the files of `src_dir` by default, or git)
+    #[serde(default)]
+    pub source: BundleSource,
+    // Globs relative to `src_dir` (all the files when empty)
+    #[serde(default)]
     pub src_globs: Vec<String>,
+    // Matched like `src_globs` (relative to `src_dir`), the matching files are not bundled
+    #[serde(default)]
+    pub exclude_globs: Vec<String>,
+    // Skip the files ignored by `.gitignore`, `.ignore` and the global git excludes
+    #[serde(default = "default_true")]
+    pub respect_ignore_files: bool,
+    // How the files are written in the bundle (`commented`, `markdown`, `xml` or `jsonl`)
+    #[serde(default)]
+    pub format: BundleFormat,
+    // Above this size (in KB), a file is skipped (and reported at the end of the sync)
+    pub max_file_size_kb: Option<u64>,
+    // Encoding of the text files (e.g., `latin1`), the invalid sequences are replaced
+    pub encoding: Option<String>,
+    // Above this size (in KB), the bundle is split into numbered parts, uploaded as separate files
+    pub max_size_kb: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(super) enum BundleMode {
+    #[default]
+    Bundle,
+    PerFile,
+}
+
+// * Source of the bundle, e.g., `source = { type = "git_changed", base = "main" }`
+// - The git sources run git in `src_dir`, and their paths are limited to it
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(super) enum BundleSource {
+    // The files of `src_dir` matching the globs
+    #[default]
+    Files,
+    // The files changed since the branch forked from `base` (committed or not, and the untracked ones)
+    GitChanged {
+        base: String,
+    },
+    // The `git diff` of the working tree, or of the staged changes
+    GitDiff {
+        #[serde(default)]
+        staged: bool,
+    },
+    // The summary of the last `count` commits
+    GitLog {
+        count: usize,
+    },
+    // The output of a command, run with the shell (e.g., `cargo tree`)
+    Command {
+        command: String,
+        // Relative to the buddy dir (`src_dir` by default)
+        working_dir: Option<String>,
+        #[serde(default = "default_command_timeout_secs")]
+        timeout_secs: u64,
+        // The output is reused until it is older than `ttl_secs` (run on every sync by default)
+        ttl_secs: Option<u64>,
+    },
+}
+
+fn default_src_dir() -> String {
+    ".".to_string()
+}
+
+fn default_command_timeout_secs() -> u64 {
+    60
+}
+
+fn default_true() -> bool {
+    true
+}
+
+// Impls
+impl FileBundle {
+    // * Returns the globs filter of the bundle, matched against the paths relative to `src_dir`
+    pub fn glob_filter(&self) -> Result<GlobFilter> {
+        let (src_globs, exclude_globs) = self.globs();
+        GlobFilter::new(Some(&src_globs), Some(&exclude_globs))
+    }
+
+    // * Returns the bundler settings of the bundle
+    pub fn bundle_options<'a>(&'a self, root_dir: &'a Path) -> Result<BundleOptions<'a>> {
+        let encoding = match self.encoding.as_deref() {
+            Some(label) => Encoding::for_label(label.as_bytes()).ok_or_else(|| {
+                format!(
+                    "Unknown encoding '{label}' for the bundle '{}'",
+                    self.bundle_name
+                )
+            })?,
+            None => UTF_8,
+        };
+
+        Ok(BundleOptions {
+            name: &self.bundle_name,
+            description: self.description.as_deref(),
+            root_dir,
+            format: self.format,
+            max_size: self.max_size_kb.map(|max_size_kb| max_size_kb * 1024),
+            max_file_size: self
+                .max_file_size_kb
+                .map(|max_file_size_kb| max_file_size_kb * 1024),
+            encoding,
+        })
+    }
+
+    pub fn globs(&self) -> (Vec<&str>, Vec<&str>) {
+        let src_globs = if self.src_globs.is_empty() {
+            vec!["**"]
+        } else {
+            self.src_globs.iter().map(AsRef::as_ref).collect()
+        };
+
+        (
+            src_globs,
+            self.exclude_globs.iter().map(AsRef::as_ref).collect(),
+        )
+    }
+}
+
+fn default_title_model() -> String {
+    "gpt-3.5-turbo".to_string()
 }
 
 // Froms
@@ -0,0 +1,226 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use chrono::Utc;
+use derive_more::Deref;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ais::{assistant::ThreadId, completion::complete, OpenAIClient},
+    utils::files::{load_from_json, save_to_json},
+    Result,
+};
+
+// TODO: Define the CONSTANTS in a separate file constants.rs
+const CONVERSATIONS_JSON: &str = "conversations.json";
+// Single conversation file of the previous versions, migrated to the index
+const LEGACY_CONVERSATION_JSON: &str = "conversation.json";
+pub(super) const DEFAULT_CONVERSATION_NAME: &str = "default";
+pub(super) const NEW_CONVERSATION_PREFIX: &str = "conversation";
+const PROVISIONAL_TITLE_MAX_CHARS: usize = 40;
+const META_EXCHANGE_MAX_CHARS: usize = 4000;
+// Held while updating `conversations.json`, so the updates of the process do not drop each other
+// (e.g., the title task and a `/new`)
+// ! NOTE: Never held across an `await` (the index is reloaded after the API calls), so not a tokio one
+static INDEX_LOCK: Mutex<()> = Mutex::new(());
+
+const META_SYSTEM_PROMPT: &str = "You give short titles to conversations. \
+Reply with exactly two lines, and nothing else:
+Title: <title of at most 6 words>
+Summary: <summary of the conversation in one sentence>";
+
+#[derive(Debug, Clone, Deref, Serialize, Deserialize)]
+pub struct Conversation {
+    pub name: String,
+    #[deref]
+    pub thread_id: ThreadId,
+    // Unix timestamps (in seconds)
+    pub created_at: i64,
+    pub last_used_at: i64,
+    // Generated after the first exchange
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+}
+
+// * Index of the named conversations, saved in `.buddy/conversations.json`
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(super) struct ConversationIndex {
+    pub current: Option<String>,
+    pub conversations: Vec<Conversation>,
+}
+
+#[derive(Deserialize)]
+struct LegacyConversation {
+    thread_id: ThreadId,
+}
+
+impl Conversation {
+    pub(super) fn new(name: impl Into<String>, thread_id: ThreadId) -> Self {
+        let now = Utc::now().timestamp();
+        Self {
+            name: name.into(),
+            thread_id,
+            created_at: now,
+            last_used_at: now,
+            title: None,
+            summary: None,
+        }
+    }
+
+    // * Returns `name - title` when the conversation has a title
+    pub fn display_name(&self) -> String {
+        match self.title.as_ref() {
+            Some(title) => format!("{} - {}", self.name, title),
+            None => self.name.clone(),
+        }
+    }
+
+    // * Title made from the first user message, until the generated one is available
+    pub(super) fn provisional_title(msg: &str) -> String {
+        let msg = msg.split_whitespace().collect::<Vec<_>>().join(" ");
+        if msg.chars().count() > PROVISIONAL_TITLE_MAX_CHARS {
+            let title: String = msg.chars().take(PROVISIONAL_TITLE_MAX_CHARS).collect();
+            format!("{}...", title.trim_end())
+        } else {
+            msg
+        }
+    }
+}
+
+impl ConversationIndex {
+    // * Loads the index, or migrates the legacy `conversation.json` as the default conversation
+    pub fn load(data_dir: &Path) -> Result<Self> {
+        let index_file = data_dir.join(CONVERSATIONS_JSON);
+        if index_file.exists() {
+            return load_from_json(index_file);
+        }
+
+        let mut index = Self::default();
+
+        let legacy_file = data_dir.join(LEGACY_CONVERSATION_JSON);
+        if let Ok(legacy) = load_from_json::<LegacyConversation>(&legacy_file) {
+            index.upsert(Conversation::new(
+                DEFAULT_CONVERSATION_NAME,
+                legacy.thread_id,
+            ));
+            index.current = Some(DEFAULT_CONVERSATION_NAME.to_string());
+            index.save(data_dir)?;
+            fs::remove_file(&legacy_file)?;
+        }
+
+        Ok(index)
+    }
+
+    pub fn save(&self, data_dir: &Path) -> Result<()> {
+        save_to_json(data_dir.join(CONVERSATIONS_JSON), self)
+    }
+
+    // * Reloads the index, applies the change and saves it, under the index lock
+    pub fn update<T>(data_dir: &Path, change: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        let _guard = INDEX_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+
+        let mut index = Self::load(data_dir)?;
+        let res = change(&mut index)?;
+        index.save(data_dir)?;
+
+        Ok(res)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Conversation> {
+        self.conversations.iter().find(|conv| conv.name == name)
+    }
+
+    // * Replaces the conversation with the same name, or adds it
+    pub fn upsert(&mut self, conversation: Conversation) {
+        match self
+            .conversations
+            .iter_mut()
+            .find(|conv| conv.name == conversation.name)
+        {
+            Some(conv) => *conv = conversation,
+            None => self.conversations.push(conversation),
+        }
+    }
+
+    // * Sets the title and summary of the conversation, returns `false` if not found
+    // - Matched by name, the thread might have been replaced meanwhile (e.g., `/retry`)
+    pub fn set_meta(&mut self, name: &str, title: String, summary: Option<String>) -> bool {
+        let conversation = self.conversations.iter_mut().find(|conv| conv.name == name);
+
+        match conversation {
+            Some(conversation) => {
+                conversation.title = Some(title);
+                conversation.summary = summary;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn rename(&mut self, name: &str, new_name: &str) -> Result<Conversation> {
+        if self.get(new_name).is_some() {
+            return Err(format!("Conversation '{new_name}' already exists").into());
+        }
+        let conversation = self
+            .conversations
+            .iter_mut()
+            .find(|conv| conv.name == name)
+            .ok_or_else(|| format!("No conversation named '{name}'"))?;
+        conversation.name = new_name.to_string();
+        let conversation = conversation.clone();
+
+        if self.current.as_deref() == Some(name) {
+            self.current = Some(new_name.to_string());
+        }
+
+        Ok(conversation)
+    }
+
+    // * Returns the first free `{prefix}-{n}` name
+    pub fn next_name(&self, prefix: &str) -> String {
+        (1..)
+            .map(|num| format!("{prefix}-{num}"))
+            .find(|name| self.get(name).is_none())
+            .unwrap_or_default()
+    }
+}
+
+// Conversation Meta
+// * Generates the title and summary of the conversation from its first exchange, and saves them in the index
+pub(super) async fn generate_conversation_meta(
+    openai_client: OpenAIClient,
+    model: String,
+    data_dir: PathBuf,
+    name: String,
+    exchange: (String, String),
+) -> Result<()> {
+    let (msg, res) = exchange;
+    let exchange = format!("User: {msg}\n\nAssistant: {res}");
+    let exchange: String = exchange.chars().take(META_EXCHANGE_MAX_CHARS).collect();
+
+    let content = complete(&openai_client, &model, META_SYSTEM_PROMPT, &exchange).await?;
+
+    let mut title = None;
+    let mut summary = None;
+    for line in content.lines() {
+        if let Some(value) = line.trim().strip_prefix("Title:") {
+            title = Some(value.trim().trim_matches('"').to_string());
+        } else if let Some(value) = line.trim().strip_prefix("Summary:") {
+            summary = Some(value.trim().to_string());
+        }
+    }
+    let title = title
+        .filter(|title| !title.is_empty())
+        .ok_or_else(|| format!("No title in the completion: {content}"))?;
+
+    // Reloaded after the completion, so the changes made meanwhile are kept
+    ConversationIndex::update(&data_dir, |index| {
+        index.set_meta(&name, title, summary);
+        Ok(())
+    })
+}
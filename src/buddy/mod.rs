@@ -1,32 +1,61 @@
+mod changes;
 mod config;
+mod conversations;
+mod sources;
+mod sync;
 
 use std::{
+    collections::HashSet,
     fs,
     path::{Path, PathBuf},
+    sync::Mutex,
+    time::Duration,
 };
 
-use derive_more::{Deref, From};
-use serde::{Deserialize, Serialize};
+use chrono::Utc;
+use tokio::{sync::watch, task::JoinHandle, time::timeout};
+use tracing::{debug, info, warn};
 
 use crate::{
     ais::{
-        assistant::{self, AssistantId, ThreadId},
-        new_openai_client, OpenAIClient,
+        assistant::{self, AssistantId, FileId, RunOutput, ThreadId},
+        msg::ThreadMsg,
+        new_openai_client,
+        steps::{RunProgress, RunStep},
+        OpenAIClient,
+    },
+    usage::{
+        append_usage, check_budget, load_usage, model_price, UsageEstimate, UsageRecord,
+        UsageTotals,
     },
     utils::{
-        cli::icon_check,
-        files::{
-            bundle_to_file, ensure_dir, list_files, load_from_json, load_from_toml, read_to_string,
-            save_to_json,
+        bundle::{
+            bundle_to_files, relative_path, BundleItem, BundleOutput, SkipReason, SkippedFile,
         },
+        cli::{icon_check, icon_deleted_ok},
+        files::{ensure_dir, hash_file, list_files, load_from_toml, read_to_string, XFile},
     },
     Result,
 };
 
-use self::config::Config;
+use self::{
+    config::{BundleMode, Config, FileBundle},
+    conversations::{
+        generate_conversation_meta, ConversationIndex, DEFAULT_CONVERSATION_NAME,
+        NEW_CONVERSATION_PREFIX,
+    },
+    sources::bundle_items,
+    sync::{SyncState, SYNC_LOCK},
+};
+
+pub use self::{changes::WatchChanges, conversations::Conversation};
 
 // TODO: Define the CONSTANTS in a separate file constants.rs
 const BUDDY_TOML: &str = "buddy.toml";
+const DATA_DIR: &str = ".buddy";
+const BACKGROUND_TASKS_TIMEOUT_SEC: u64 = 10;
+const USAGE_JSONL: &str = "usage.jsonl";
+const LOGS_DIR: &str = "logs";
 
 // TODO: Implement Arc<T> to improve performance. Mayvbe will be necessary to manually implement the From trait.
 #[derive(Debug)]
@@ -35,11 +64,10 @@ pub struct Buddy {
     openai_client: OpenAIClient,
     assistant_id: AssistantId,
     config: Config,
-}
-
-#[derive(Debug, From, Deref, Serialize, Deserialize)]
-pub struct Conversation {
-    thread_id: ThreadId,
+    background_tasks: Mutex<Vec<JoinHandle<()>>>,
+    session_usage: Mutex<UsageTotals>,
+    // Live status of the current run (`None` when no run is in progress)
+    run_progress: watch::Sender<Option<RunProgress>>,
 }
 
 // * Public Functions
@@ -48,7 +76,36 @@ impl Buddy {
         &self.config.name
     }
 
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn model(&self) -> &str {
+        &self.config.model
+    }
+
+    pub fn bundle_names(&self) -> Vec<&str> {
+        self.config
+            .file_bundles
+            .iter()
+            .map(|bundle| bundle.bundle_name.as_str())
+            .collect()
+    }
+
     pub async fn init_from_dir(dir: impl AsRef<Path>, recreate_assistant: bool) -> Result<Self> {
+        let buddy = Self::load_from_dir(dir, recreate_assistant).await?;
+
+        // Upload the Instructions
+        buddy.upload_instructions().await?;
+
+        // Upload Files
+        buddy.upload_files(false).await?;
+
+        Ok(buddy)
+    }
+
+    // * Loads the Buddy without uploading the Instructions and Files (e.g., for the subcommands)
+    pub async fn load_from_dir(dir: impl AsRef<Path>, recreate_assistant: bool) -> Result<Self> {
         let dir = dir.as_ref();
 
         // Load from the Directory
@@ -65,14 +122,11 @@ impl Buddy {
             openai_client,
             assistant_id,
             config,
+            background_tasks: Mutex::new(Vec::new()),
+            session_usage: Mutex::new(UsageTotals::default()),
+            run_progress: watch::Sender::new(None),
         };
 
-        // Upload the Instructions
-        buddy.upload_instructions().await?;
-
-        // Upload Files
-        buddy.upload_files(false).await?;
-
         Ok(buddy)
     }
 
@@ -88,25 +142,41 @@ impl Buddy {
                 instruction_content,
             )
             .await?;
-            println!("{} Instructions Uploaded", icon_check());
+            info!("{} Instructions Uploaded", icon_check());
             Ok(true)
         } else {
             Ok(false)
         }
     }
 
+    // * Rebuilds the bundles, and uploads the ones whose content changed since the last sync
+    // - `recreate` uploads all the bundles, even the unchanged ones
     pub async fn upload_files(&self, recreate: bool) -> Result<u32> {
-        let mut num_uploaded = 0;
+        self.upload_bundles(recreate, None).await
+    }
+
+    // * Same as `upload_files`, but only for the bundles named in `only` (all if `None`)
+    pub async fn upload_bundles(&self, recreate: bool, only: Option<&[String]>) -> Result<u32> {
+        let _sync_guard = SYNC_LOCK.lock().await;
 
         // Get the .buddy/files
         let data_files_dir = self.data_files_dir()?;
 
         // Clean the .buddy/files left over by Old Buddy
         let exclude_element = format!("*{}*", &self.assistant_id);
+        let bundle_globs: Vec<String> = self
+            .config
+            .file_bundles
+            .iter()
+            .filter(|bundle| !bundle.dst_ext.is_empty())
+            .map(|bundle| format!("*.{}", bundle.dst_ext))
+            .collect();
+        let bundle_globs: Vec<&str> = bundle_globs.iter().map(AsRef::as_ref).collect();
         for file in list_files(
             &data_files_dir,
-            Some(&["*.rs", "*.md"]),
+            Some(&bundle_globs),
             Some(&[&exclude_element]),
+            false,
         )? {
             // Delete the file
             let file_str = file.to_string_lossy();
@@ -117,100 +187,780 @@ impl Buddy {
             fs::remove_file(&file)?;
         }
 
-        // Generate and Upload the .buddy/files bundle files
-        for bundle in self.config.file_bundles.iter() {
+        // Load the Sync Manifest, and the files still attached to the Assistant
+        let remote_files =
+            assistant::get_files_hashmap(&self.openai_client, &self.assistant_id).await?;
+        let mut sync = SyncState::new(self.data_dir()?, remote_files, recreate)?;
+        let sources_dir = self.data_dir()?.join("sources");
+        ensure_dir(&sources_dir)?;
+
+        // Generate and Upload the .buddy/files bundle files (or the files themselves)
+        let bundles = self.config.file_bundles.iter().filter(|bundle| {
+            only.map(|names| names.contains(&bundle.bundle_name))
+                .unwrap_or(true)
+        });
+        for bundle in bundles {
             let src_dir = self.dir.join(&bundle.src_dir);
 
             if src_dir.is_dir() {
-                let src_globs: Vec<&str> = bundle.src_globs.iter().map(AsRef::as_ref).collect();
-
-                let files = list_files(&src_dir, Some(&src_globs), None)?;
-
-                if !files.is_empty() {
-                    // Compute the Bundle File Name
-                    let bundle_file_name = format!(
-                        "{}-{}-bundle-{}.{}",
-                        self.name(),
-                        bundle.bundle_name,
-                        self.assistant_id,
-                        bundle.dst_ext
-                    );
-                    let bundle_file = self.data_files_dir()?.join(bundle_file_name);
-
-                    // If it doesn't exists, then force a reupload
-                    let force_reupload = recreate || !bundle_file.exists();
-
-                    // Rebundle no matter if it exists or not
-                    bundle_to_file(files, &bundle_file)?;
-
-                    // Upload
-                    let (_, uploaded) = assistant::upload_file_by_name(
-                        &self.openai_client,
-                        &self.assistant_id,
-                        &bundle_file,
-                        force_reupload,
-                    )
-                    .await?;
-
-                    if uploaded {
-                        num_uploaded += 1;
+                // A failed source (e.g., a command timeout, or not a git repo) does not stop the sync
+                let items = match bundle_items(bundle, &self.dir, &sources_dir).await {
+                    Ok(items) => items,
+                    Err(err) => {
+                        sync.failed_bundles
+                            .push((bundle.bundle_name.clone(), err.to_string()));
+                        continue;
                     }
+                };
+
+                match bundle.mode {
+                    // ! NOTE: Synced even without items, to remove the remote files
+                    BundleMode::Bundle => self.sync_bundle(bundle, items, &mut sync).await?,
+                    BundleMode::PerFile => self.sync_per_file(bundle, items, &mut sync).await?,
                 }
             }
         }
 
-        Ok(num_uploaded)
+        // ! NOTE: The entries of the other bundles are only known when all the bundles were synced
+        //         (the entries of the failed bundles are kept, like their remote files)
+        if only.is_none() && sync.failed_bundles.is_empty() {
+            sync.manifest.retain(&sync.entry_names);
+            sync.manifest.save(&sync.data_dir)?;
+        }
+
+        if !sync.skipped_files.is_empty() {
+            let skipped_files: Vec<String> = sync
+                .skipped_files
+                .iter()
+                .map(|skipped| format!("  - {} ({})", skipped.path, skipped.reason))
+                .collect();
+            warn!(
+                "{} Files Skipped in the Bundles:\n{}",
+                skipped_files.len(),
+                skipped_files.join("\n")
+            );
+        }
+
+        if !sync.failed_bundles.is_empty() {
+            let failed_bundles: Vec<String> = sync
+                .failed_bundles
+                .iter()
+                .map(|(bundle_name, err)| format!("  - {bundle_name} ({err})"))
+                .collect();
+            warn!(
+                "{} Bundles Not Synced (the previous ones are kept):\n{}",
+                failed_bundles.len(),
+                failed_bundles.join("\n")
+            );
+        }
+
+        Ok(sync.num_uploaded)
     }
 
+    // * Loads the current conversation, or creates it if needed
+    // - `recreate` replaces the thread of the current conversation by a new one
     pub async fn load_or_create_conversation(&self, recreate: bool) -> Result<Conversation> {
-        let conversation_file = self.data_dir()?.join("conversation.json");
+        let index = ConversationIndex::load(&self.data_dir()?)?;
+        let name = index
+            .current
+            .clone()
+            .unwrap_or_else(|| DEFAULT_CONVERSATION_NAME.to_string());
+
+        self.open_conversation(&index, &name, recreate).await
+    }
 
-        if recreate && conversation_file.exists() {
-            fs::remove_file(&conversation_file)?;
+    // * Makes the conversation `name` the current one
+    // - If `create` is `true`, creates it when it does not exist
+    pub async fn switch_conversation(&self, name: &str, create: bool) -> Result<Conversation> {
+        let index = ConversationIndex::load(&self.data_dir()?)?;
+        if !create && index.get(name).is_none() {
+            return Err(format!("No conversation named '{name}'").into());
         }
 
-        let conversation =
-            if let Ok(conversation) = load_from_json::<Conversation>(&conversation_file) {
-                assistant::get_thread(&self.openai_client, &conversation.thread_id)
-                    .await
-                    .map_err(|_| format!("Cannot find thread_id for {:?}", conversation))?;
-                println!("{} Conversation Loaded", icon_check());
-                conversation
-            } else {
-                let thread_id = assistant::create_thread(&self.openai_client).await?;
-                println!("{} Conversation Created", icon_check());
-                let conversation = thread_id.into();
-                save_to_json(&conversation_file, &conversation)?;
-                conversation
-            };
+        self.open_conversation(&index, name, false).await
+    }
 
-        Ok(conversation)
+    // * Creates a new conversation and makes it the current one
+    pub async fn new_conversation(&self, name: Option<&str>) -> Result<Conversation> {
+        let index = ConversationIndex::load(&self.data_dir()?)?;
+        let name = name
+            .map(str::to_string)
+            .unwrap_or_else(|| index.next_name(NEW_CONVERSATION_PREFIX));
+        if index.get(&name).is_some() {
+            return Err(format!("Conversation '{name}' already exists").into());
+        }
+
+        self.open_conversation(&index, &name, false).await
+    }
+
+    // * Creates a new conversation with the first `index` messages of `conversation` (all if `None`),
+    //   and makes it the current one. The original conversation is left untouched.
+    // - A negative `index` counts from the newest message (`-1` keeps all the messages)
+    pub async fn fork_conversation(
+        &self,
+        conversation: &Conversation,
+        index: Option<isize>,
+    ) -> Result<Conversation> {
+        let msgs =
+            assistant::list_thread_msgs(&self.openai_client, &conversation.thread_id).await?;
+        let num_msgs = match index {
+            None => Some(msgs.len()),
+            Some(index) if index > 0 => Some(index.unsigned_abs()),
+            Some(index) => msgs.len().checked_sub(index.unsigned_abs() - 1),
+        };
+        let Some(num_msgs) = num_msgs.filter(|num_msgs| (1..=msgs.len()).contains(num_msgs)) else {
+            return Err(format!(
+                "Invalid message index {}, expected 1 to {len} or -1 to -{len}",
+                index.unwrap_or_default(),
+                len = msgs.len()
+            )
+            .into());
+        };
+
+        let thread_id =
+            assistant::create_thread_with_msgs(&self.openai_client, &msgs[..num_msgs]).await?;
+
+        let fork = ConversationIndex::update(&self.data_dir()?, |index| {
+            let name = index.next_name(&format!("{}-fork", conversation.name));
+            let fork = Conversation::new(name, thread_id);
+            index.upsert(fork.clone());
+            index.current = Some(fork.name.clone());
+            Ok(fork)
+        })?;
+
+        info!(
+            "{} Conversation '{}' Forked from '{}' ({num_msgs} messages)",
+            icon_check(),
+            fork.name,
+            conversation.name
+        );
+
+        Ok(fork)
     }
 
-    pub async fn chat(&self, conversation: &Conversation, msg: &str) -> Result<String> {
-        let res = assistant::run_thread_msg(
+    // * Returns the conversations, the most recently used first, and the current conversation name
+    pub fn list_conversations(&self) -> Result<(Vec<Conversation>, Option<String>)> {
+        let mut index = ConversationIndex::load(&self.data_dir()?)?;
+        index
+            .conversations
+            .sort_by_key(|conv| std::cmp::Reverse(conv.last_used_at));
+
+        Ok((index.conversations, index.current))
+    }
+
+    // * Returns the conversation `name` (the current one if `None`), without creating or opening it
+    pub fn find_conversation(&self, name: Option<&str>) -> Result<Conversation> {
+        let index = ConversationIndex::load(&self.data_dir()?)?;
+        let name = name
+            .map(str::to_string)
+            .or_else(|| index.current.clone())
+            .unwrap_or_else(|| DEFAULT_CONVERSATION_NAME.to_string());
+
+        index
+            .get(&name)
+            .cloned()
+            .ok_or_else(|| format!("No conversation named '{name}'").into())
+    }
+
+    pub fn rename_conversation(&self, name: &str, new_name: &str) -> Result<Conversation> {
+        ConversationIndex::update(&self.data_dir()?, |index| index.rename(name, new_name))
+    }
+
+    // * Runs the message on the conversation thread, and returns the answer
+    // - After the first exchange, the conversation gets a provisional title,
+    //   while the generated title and summary are saved in the background
+    pub async fn chat(&self, conversation: &mut Conversation, msg: &str) -> Result<String> {
+        self.check_budget(conversation, msg)?;
+
+        let run_output = assistant::run_thread_msg(
             &self.openai_client,
             &self.assistant_id,
             &conversation.thread_id,
             msg,
+            |progress| {
+                self.run_progress.send_replace(Some(progress.clone()));
+            },
         )
-        .await?;
+        .await;
+        self.run_progress.send_replace(None);
+        let run_output = run_output?;
+        self.record_usage(conversation, &run_output)?;
+        let res = run_output.text;
+
+        if conversation.title.is_none() {
+            let data_dir = self.data_dir()?;
+            let title = Conversation::provisional_title(msg);
+            ConversationIndex::update(&data_dir, |index| {
+                index.set_meta(&conversation.name, title.clone(), None);
+                Ok(())
+            })?;
+            conversation.title = Some(title);
+
+            let task = generate_conversation_meta(
+                self.openai_client.clone(),
+                self.config.title_model.clone(),
+                data_dir,
+                conversation.name.clone(),
+                (msg.to_string(), res.clone()),
+            );
+            self.spawn_background(async move {
+                // ! NOTE: On error, the provisional title is kept
+                // (Logged only in the log file, to not print over the prompt)
+                if let Err(err) = task.await {
+                    debug!("Cannot generate the conversation title - {err}");
+                }
+            });
+        }
+
+        Ok(res)
+    }
+
+    // * Removes the last user/assistant exchange of the conversation, and returns the removed user message
+    // ! NOTE: The Assistants API (v1) cannot delete messages, so the thread is rebuilt without the exchange
+    //         (the old thread is then deleted)
+    pub async fn undo_last_exchange(&self, conversation: &mut Conversation) -> Result<String> {
+        let (thread_id, msg) = self.thread_without_last_exchange(conversation).await?;
+        self.replace_thread(conversation, thread_id).await?;
+
+        Ok(msg)
+    }
+
+    // * Runs the assistant again on the last user message, replacing the last answer in the conversation
+    // - The new answer is run on a new thread, the conversation is only updated if it succeeds
+    pub async fn retry_last_exchange(&self, conversation: &mut Conversation) -> Result<String> {
+        let (thread_id, msg) = self.thread_without_last_exchange(conversation).await?;
+
+        let mut retried = conversation.clone();
+        retried.thread_id = thread_id;
+        let res = match self.chat(&mut retried, &msg).await {
+            Ok(res) => res,
+            Err(err) => {
+                let _ = assistant::delete_thread(&self.openai_client, &retried.thread_id).await;
+                return Err(err);
+            }
+        };
+
+        // The title might have been set by `chat`
+        conversation.title = retried.title;
+        self.replace_thread(conversation, retried.thread_id).await?;
 
         Ok(res)
     }
+
+    // * Returns a receiver of the live status of the runs (e.g., to show it while waiting for `chat`)
+    pub fn run_progress(&self) -> watch::Receiver<Option<RunProgress>> {
+        self.run_progress.subscribe()
+    }
+
+    // * Returns the steps of the latest run of the conversation (i.e., of the last answer)
+    pub async fn last_run_steps(&self, conversation: &Conversation) -> Result<Vec<RunStep>> {
+        let run_id = assistant::get_last_run_id(&self.openai_client, &conversation.thread_id)
+            .await?
+            .ok_or("No run in this conversation yet")?;
+
+        assistant::list_run_steps(&self.openai_client, &conversation.thread_id, &run_id).await
+    }
+
+    // * Returns the usage records of this buddy
+    pub fn usage_records(&self) -> Result<Vec<UsageRecord>> {
+        Self::load_usage_records(&self.dir)
+    }
+
+    // * Returns the logs dir of the buddy dir, to setup the logging before loading the buddy
+    // - `None` when `dir` is not a buddy dir (no `buddy.toml`), so nothing is created in it
+    pub fn logs_dir(dir: impl AsRef<Path>) -> Result<Option<PathBuf>> {
+        let dir = dir.as_ref();
+        if !dir.join(BUDDY_TOML).is_file() {
+            return Ok(None);
+        }
+
+        let logs_dir = dir.join(DATA_DIR).join(LOGS_DIR);
+        ensure_dir(&logs_dir)?;
+        Ok(Some(logs_dir))
+    }
+
+    // * Returns the usage records of the buddy dir, without loading the buddy (e.g., for the subcommands)
+    pub fn load_usage_records(dir: impl AsRef<Path>) -> Result<Vec<UsageRecord>> {
+        load_usage(&dir.as_ref().join(DATA_DIR).join(USAGE_JSONL))
+    }
+
+    // * Returns the usage totals of the runs since the buddy was loaded
+    pub fn session_usage(&self) -> UsageTotals {
+        self.session_usage
+            .lock()
+            .map(|totals| totals.clone())
+            .unwrap_or_default()
+    }
+
+    // * Waits (with a timeout) for the background tasks, so they are not dropped on quit
+    pub async fn wait_background_tasks(&self) {
+        let tasks: Vec<JoinHandle<()>> = match self.background_tasks.lock() {
+            Ok(mut tasks) => tasks.drain(..).collect(),
+            Err(_) => return,
+        };
+
+        let all_tasks = async {
+            for task in tasks {
+                let _ = task.await;
+            }
+        };
+        let _ = timeout(Duration::from_secs(BACKGROUND_TASKS_TIMEOUT_SEC), all_tasks).await;
+    }
+
+    // * Returns the conversation as saved in the index (e.g., with the generated title)
+    pub fn reload_conversation(&self, conversation: &Conversation) -> Result<Conversation> {
+        let index = ConversationIndex::load(&self.data_dir()?)?;
+        let conversation = index
+            .get(&conversation.name)
+            .cloned()
+            .unwrap_or_else(|| conversation.clone());
+
+        Ok(conversation)
+    }
+
+    // * Returns all the messages of the conversation, oldest first, with the citations file names
+    pub async fn conversation_msgs(&self, conversation: &Conversation) -> Result<Vec<ThreadMsg>> {
+        let mut msgs =
+            assistant::list_thread_msgs(&self.openai_client, &conversation.thread_id).await?;
+        self.resolve_citations(&mut msgs).await?;
+
+        Ok(msgs)
+    }
+
+    // * Returns a page of the conversation messages, newest first, and if there are older messages
+    // - The citations file names are resolved for the page only
+    pub async fn conversation_msgs_page(
+        &self,
+        conversation: &Conversation,
+        limit: usize,
+        after: Option<&str>,
+    ) -> Result<(Vec<ThreadMsg>, bool)> {
+        let (mut msgs, has_more) = assistant::list_thread_msgs_page(
+            &self.openai_client,
+            &conversation.thread_id,
+            limit,
+            after,
+        )
+        .await?;
+        self.resolve_citations(&mut msgs).await?;
+
+        Ok((msgs, has_more))
+    }
+
+    // * Returns the paths to watch for the watch mode, with `true` when watched recursively
+    // - The dirs of `buddy.toml` and of the instructions are watched (not the files),
+    //   as some editors save a file by replacing it
+    pub fn watched_paths(&self) -> Vec<(PathBuf, bool)> {
+        let mut paths = vec![(self.dir.clone(), false)];
+
+        let instructions_file = self.dir.join(&self.config.instructions_file);
+        if let Some(instructions_dir) = instructions_file.parent() {
+            paths.push((instructions_dir.to_path_buf(), false));
+        }
+        for bundle in self.config.file_bundles.iter() {
+            paths.push((self.dir.join(&bundle.src_dir), true));
+        }
+
+        paths
+    }
+
+    // * Returns what the changed paths affect (the config, the instructions, and which bundles)
+    pub fn watch_changes(&self, changed: &HashSet<PathBuf>) -> Result<WatchChanges> {
+        WatchChanges::new(&self.dir, &self.dir.join(DATA_DIR), &self.config, changed)
+    }
+
+    pub fn history_on_start(&self) -> usize {
+        self.config.history_on_start
+    }
+
+    pub fn history_file(&self) -> Result<PathBuf> {
+        Ok(self.data_dir()?.join("history"))
+    }
 }
 
 // * Private Functions
 impl Buddy {
+    // * Bundles the files (split in parts if too large), and uploads the changed parts
+    async fn sync_bundle(
+        &self,
+        bundle: &FileBundle,
+        items: Vec<BundleItem>,
+        sync: &mut SyncState,
+    ) -> Result<()> {
+        if bundle.dst_ext.is_empty() {
+            return Err(format!("The bundle '{}' has no `dst_ext`", bundle.bundle_name).into());
+        }
+
+        // Compute the Bundle File Name
+        let bundle_file_stem = format!(
+            "{}-{}-bundle-{}",
+            self.name(),
+            bundle.bundle_name,
+            self.assistant_id
+        );
+        let bundle_file_name = format!("{bundle_file_stem}.{}", bundle.dst_ext);
+        let bundle_file = self.data_files_dir()?.join(&bundle_file_name);
+        sync.entry_names.insert(bundle_file_name.clone());
+
+        // Rebundle no matter if it exists or not (split in parts if too large)
+        // - Without items (e.g., no changed files), the previous bundle is removed below,
+        //   so the assistant does not answer from an outdated one
+        let part_files = if items.is_empty() {
+            let _ = fs::remove_file(&bundle_file);
+            Vec::new()
+        } else {
+            let root_dir = self.project_dir();
+            let bundle_options = bundle.bundle_options(&root_dir)?;
+            let BundleOutput {
+                part_files,
+                skipped,
+            } = bundle_to_files(items, &bundle_file, &bundle_options)?;
+            sync.skipped_files.extend(skipped);
+            part_files
+        };
+        let part_file_names: Vec<String> = part_files
+            .iter()
+            .map(|part_file| part_file.x_file_name().to_string())
+            .collect();
+
+        for part_file in part_files.iter() {
+            self.upload_if_changed(&bundle_file_name, part_file.x_file_name(), part_file, sync)
+                .await?;
+        }
+
+        // Delete the parts not produced anymore (e.g., the bundle got smaller, or has no items)
+        let part_prefix = format!("{bundle_file_stem}-part-");
+        let part_suffix = format!(".{}", bundle.dst_ext);
+        for (file_name, file_id) in sync.remote_files.iter() {
+            let is_bundle_part = *file_name == bundle_file_name
+                || (file_name.starts_with(&part_prefix) && file_name.ends_with(&part_suffix));
+            if !is_bundle_part || part_file_names.contains(file_name) {
+                continue;
+            }
+
+            assistant::delete_file(&self.openai_client, &self.assistant_id, file_id, file_name)
+                .await;
+            // Also remove the local file, if still there
+            let _ = fs::remove_file(self.data_files_dir()?.join(file_name));
+            info!(
+                "{} Bundle Part Removed - '{}'",
+                icon_deleted_ok(),
+                file_name
+            );
+        }
+        sync.manifest
+            .retain_parts(&bundle_file_name, &part_file_names);
+        sync.manifest.save(&sync.data_dir)?;
+
+        Ok(())
+    }
+
+    // * Uploads each changed file under its own name, and deletes the remote files not matched anymore
+    async fn sync_per_file(
+        &self,
+        bundle: &FileBundle,
+        items: Vec<BundleItem>,
+        sync: &mut SyncState,
+    ) -> Result<()> {
+        let files = items
+            .into_iter()
+            .map(|item| match item {
+                BundleItem::File(file) => Ok(file),
+                BundleItem::Generated { path, .. } => Err(format!(
+                    "The bundle '{}' cannot upload '{path}' in the `per_file` mode, use the `bundle` mode",
+                    bundle.bundle_name
+                )),
+            })
+            .collect::<std::result::Result<Vec<PathBuf>, String>>()?;
+
+        let entry_name = format!(
+            "{}-{}-files-{}",
+            self.name(),
+            bundle.bundle_name,
+            self.assistant_id
+        );
+        sync.entry_names.insert(entry_name.clone());
+
+        let root_dir = self.project_dir();
+        let src_dir = self.dir.join(&bundle.src_dir);
+        let data_files_dir = self.data_files_dir()?;
+        let max_file_size = bundle
+            .max_file_size_kb
+            .map(|max_file_size_kb| max_file_size_kb * 1024);
+        // Paths relative to the bundle dir (the manifest parts of the entry)
+        let mut rel_paths: Vec<String> = Vec::new();
+        let mut file_names: Vec<String> = Vec::new();
+
+        for file in files {
+            let rel_path = relative_path(&file, &src_dir);
+            let file_name = per_file_upload_name(&bundle.bundle_name, &rel_path);
+            let size = fs::metadata(&file)?.len();
+            let skip_reason = if file_names.contains(&file_name) {
+                Some(SkipReason::DuplicateName)
+            } else {
+                max_file_size
+                    .filter(|max_file_size| size > *max_file_size)
+                    .map(|max_file_size| SkipReason::TooLarge(size, max_file_size))
+            };
+            if let Some(reason) = skip_reason {
+                sync.skipped_files.push(SkippedFile {
+                    path: relative_path(&file, &root_dir),
+                    reason,
+                });
+                continue;
+            }
+
+            // Uploaded from a copy, as the uploaded file name is the local one
+            let upload_file = data_files_dir.join(&file_name);
+            fs::copy(&file, &upload_file)?;
+            self.upload_if_changed(&entry_name, &rel_path, &upload_file, sync)
+                .await?;
+            rel_paths.push(rel_path);
+            file_names.push(file_name);
+        }
+
+        // Delete the remote files of the removed (or not matched anymore) files
+        let removed_files: Vec<(String, String)> = sync
+            .manifest
+            .parts(&entry_name)
+            .iter()
+            .filter(|part| !rel_paths.contains(&part.file_name))
+            .map(|part| (part.file_name.clone(), part.file_id.clone()))
+            .collect();
+        for (rel_path, file_id) in removed_files {
+            let file_name = per_file_upload_name(&bundle.bundle_name, &rel_path);
+            if sync.remote_file_ids.contains(&file_id) {
+                let file_id = FileId::from(file_id);
+                assistant::delete_file(
+                    &self.openai_client,
+                    &self.assistant_id,
+                    &file_id,
+                    &file_name,
+                )
+                .await;
+                info!("{} File Removed - '{}'", icon_deleted_ok(), file_name);
+            }
+            let _ = fs::remove_file(data_files_dir.join(&file_name));
+        }
+        sync.manifest.retain_parts(&entry_name, &rel_paths);
+        sync.manifest.save(&sync.data_dir)?;
+
+        Ok(())
+    }
+
+    // * Uploads the file, unless the manifest entry has it with the same content
+    async fn upload_if_changed(
+        &self,
+        entry_name: &str,
+        part_name: &str,
+        file: &Path,
+        sync: &mut SyncState,
+    ) -> Result<()> {
+        // Skip the Upload if the content did not change
+        let hash = hash_file(file)?;
+        if !sync.recreate
+            && sync
+                .manifest
+                .is_synced(entry_name, part_name, &hash, &sync.remote_file_ids)
+        {
+            debug!("File '{}' Unchanged", part_name);
+            return Ok(());
+        }
+
+        // Upload (Replacing the remote file with the same name, if any)
+        let (file_id, _) = assistant::upload_file_by_name(
+            &self.openai_client,
+            &self.assistant_id,
+            file,
+            &mut sync.remote_files,
+            true,
+        )
+        .await?;
+        sync.remote_file_ids.insert(file_id.to_string());
+
+        // Saved after each upload, so a failed sync does not upload them again
+        sync.manifest
+            .set_part(entry_name, part_name, hash, &file_id);
+        sync.manifest.save(&sync.data_dir)?;
+        sync.num_uploaded += 1;
+
+        Ok(())
+    }
+
+    // * Refuses the run when a budget cap is reached, and prints the warnings of the caps close to it
+    // - The next run usage is estimated from the last run of the conversation (as its thread is sent again),
+    //   plus the new message (~4 chars per token)
+    fn check_budget(&self, conversation: &Conversation, msg: &str) -> Result<()> {
+        let Some(budget) = self.config.budget.as_ref() else {
+            return Ok(());
+        };
+
+        let records = self.usage_records()?;
+        // ! NOTE: By name, the thread is replaced on `/retry` and `/undo` (the context is mostly the same)
+        let last_run = records
+            .iter()
+            .rev()
+            .find(|record| record.conversation == conversation.name);
+        let tokens = last_run
+            .map(|record| record.prompt_tokens + record.completion_tokens)
+            .unwrap_or(0)
+            + (msg.len() as u64).div_ceil(4);
+        let model = last_run
+            .map(|record| record.model.as_str())
+            .unwrap_or(&self.config.model);
+        let cost = model_price(&self.config.prices, model)
+            .map(|price| tokens as f64 * price.prompt / 1000.);
+
+        for warning in check_budget(budget, &records, &UsageEstimate { tokens, cost })? {
+            warn!("{warning}");
+        }
+
+        Ok(())
+    }
+
+    // * Appends the usage of the run to `.buddy/usage.jsonl`, and adds it to the session totals
+    fn record_usage(&self, conversation: &Conversation, run_output: &RunOutput) -> Result<()> {
+        let Some(usage) = run_output.usage.as_ref() else {
+            return Ok(());
+        };
+
+        let record = UsageRecord::new(
+            self.name(),
+            &conversation.name,
+            &conversation.thread_id,
+            &run_output.model,
+            usage.prompt_tokens,
+            usage.completion_tokens,
+            &self.config.prices,
+        );
+        append_usage(&self.data_dir()?.join(USAGE_JSONL), &record)?;
+
+        if let Ok(mut totals) = self.session_usage.lock() {
+            totals.add(&record);
+        }
+
+        Ok(())
+    }
+
+    fn spawn_background(&self, task: impl std::future::Future<Output = ()> + Send + 'static) {
+        let handle = tokio::spawn(task);
+        if let Ok(mut tasks) = self.background_tasks.lock() {
+            // Drop the handles of the finished tasks
+            tasks.retain(|task| !task.is_finished());
+            tasks.push(handle);
+        }
+    }
+
+    // * Loads (or creates) the conversation `name`, makes it current, and saves the index
+    // - `index` is only read, the saved index is reloaded after the API calls
+    async fn open_conversation(
+        &self,
+        index: &ConversationIndex,
+        name: &str,
+        recreate: bool,
+    ) -> Result<Conversation> {
+        let existing = index.get(name).filter(|_| !recreate).cloned();
+
+        let mut conversation = if let Some(conversation) = existing {
+            assistant::get_thread(&self.openai_client, &conversation.thread_id)
+                .await
+                .map_err(|_| format!("Cannot find thread_id for {:?}", conversation))?;
+            info!("{} Conversation '{}' Loaded", icon_check(), name);
+            conversation
+        } else {
+            let thread_id = assistant::create_thread(&self.openai_client).await?;
+            info!("{} Conversation '{}' Created", icon_check(), name);
+            Conversation::new(name, thread_id)
+        };
+
+        conversation.last_used_at = Utc::now().timestamp();
+        ConversationIndex::update(&self.data_dir()?, |index| {
+            index.upsert(conversation.clone());
+            index.current = Some(name.to_string());
+            Ok(())
+        })?;
+
+        Ok(conversation)
+    }
+
+    // * Sets the file names of the citations (the files are only listed if there are citations)
+    async fn resolve_citations(&self, msgs: &mut [ThreadMsg]) -> Result<()> {
+        let has_citations = msgs.iter().any(|msg| !msg.citations.is_empty());
+        if has_citations {
+            let file_name_by_id = assistant::get_file_names_by_id(&self.openai_client).await?;
+            for citation in msgs.iter_mut().flat_map(|msg| msg.citations.iter_mut()) {
+                citation.file_name = file_name_by_id.get(&citation.file_id).cloned();
+            }
+        }
+
+        Ok(())
+    }
+
+    // * Creates a new thread with the messages of the conversation before its last user message,
+    //   and returns it with this message. The conversation is left untouched.
+    async fn thread_without_last_exchange(
+        &self,
+        conversation: &Conversation,
+    ) -> Result<(ThreadId, String)> {
+        let mut msgs =
+            assistant::list_thread_msgs(&self.openai_client, &conversation.thread_id).await?;
+
+        let last_user_idx = msgs
+            .iter()
+            .rposition(|msg| msg.role == "user")
+            .ok_or("Nothing to undo in this conversation")?;
+        let last_user_msg = msgs[last_user_idx].text.clone();
+        msgs.truncate(last_user_idx);
+
+        let thread_id = assistant::create_thread_with_msgs(&self.openai_client, &msgs).await?;
+
+        Ok((thread_id, last_user_msg))
+    }
+
+    // * Sets the thread of the conversation, saves it in the index, and deletes the old thread
+    async fn replace_thread(
+        &self,
+        conversation: &mut Conversation,
+        thread_id: ThreadId,
+    ) -> Result<()> {
+        let old_thread_id = std::mem::replace(&mut conversation.thread_id, thread_id);
+
+        ConversationIndex::update(&self.data_dir()?, |index| {
+            index.upsert(conversation.clone());
+            Ok(())
+        })?;
+
+        // ! NOTE: Might already be deleted, the conversation does not use it anymore
+        let _ = assistant::delete_thread(&self.openai_client, &old_thread_id).await;
+
+        Ok(())
+    }
+
     fn data_dir(&self) -> Result<PathBuf> {
-        let data_dir = self.dir.join(".buddy");
+        let data_dir = self.dir.join(DATA_DIR);
         ensure_dir(&data_dir)?;
         Ok(data_dir)
     }
 
+    // * Returns the root of the project, the paths in the bundles are relative to it
+    // - The git repository containing the buddy dir, or the buddy dir itself
+    fn project_dir(&self) -> PathBuf {
+        let dir = self.dir.canonicalize().unwrap_or_else(|_| self.dir.clone());
+        dir.ancestors()
+            .find(|ancestor| ancestor.join(".git").exists())
+            .map(Path::to_path_buf)
+            .unwrap_or(dir)
+    }
+
     fn data_files_dir(&self) -> Result<PathBuf> {
         let dir = self.data_dir()?.join("files");
         ensure_dir(&dir)?;
         Ok(dir)
     }
 }
+
+// * Returns the remote file name of a `per_file` bundle file, unique across the bundles and dirs
+// - e.g., `src/a/mod.rs` of the bundle `code` is uploaded as `code--src__a__mod.rs`
+fn per_file_upload_name(bundle_name: &str, rel_path: &str) -> String {
+    format!("{bundle_name}--{}", rel_path.replace('/', "__"))
+}
@@ -0,0 +1,208 @@
+use std::{fs, path::Path, process::Stdio, time::Duration};
+
+use sha2::{Digest, Sha256};
+use tokio::{process::Command, time::timeout};
+use tracing::debug;
+
+use crate::{
+    utils::{
+        bundle::BundleItem,
+        files::list_files,
+        git::{changed_files, run_git},
+    },
+    Result,
+};
+
+use super::config::{BundleSource, FileBundle};
+
+// TODO: Define the CONSTANTS in a separate file constants.rs
+// Bytes of the command hash in the cache file name (e.g., `deps-3f2a9c1e0b7d4a65.out`)
+const CACHE_KEY_BYTES: usize = 8;
+
+// Bundle Sources
+// * Returns what the bundle is made of, from its source (the files of `src_dir` by default)
+// - `dir` is the buddy dir, and `cache_dir` keeps the command outputs (for their TTL)
+pub(super) async fn bundle_items(
+    bundle: &FileBundle,
+    dir: &Path,
+    cache_dir: &Path,
+) -> Result<Vec<BundleItem>> {
+    let src_dir = dir.join(&bundle.src_dir);
+    let src_dir = src_dir.as_path();
+
+    let items = match &bundle.source {
+        BundleSource::Files => {
+            let (src_globs, exclude_globs) = bundle.globs();
+            list_files(
+                src_dir,
+                Some(&src_globs),
+                Some(&exclude_globs),
+                bundle.respect_ignore_files,
+            )?
+            .into_iter()
+            .map(BundleItem::File)
+            .collect()
+        }
+        BundleSource::GitChanged { base } => {
+            let glob_filter = bundle.glob_filter()?;
+            changed_files(src_dir, base)
+                .await?
+                .into_iter()
+                .filter(|path| glob_filter.is_match(Path::new(path)))
+                .map(|path| src_dir.join(path))
+                .filter(|file| file.is_file())
+                .map(BundleItem::File)
+                .collect()
+        }
+        BundleSource::GitDiff { staged } => {
+            let mut args = vec!["diff", "--relative"];
+            if *staged {
+                args.push("--staged");
+            }
+            let diff = run_git(src_dir, &args).await?;
+            vec![generated_item(
+                format!("git {}", args.join(" ")),
+                "diff",
+                diff,
+            )]
+        }
+        BundleSource::GitLog { count } => {
+            let count = format!("-{count}");
+            let args = ["log", &count, "--date=iso", "--stat"];
+            let log = run_git(src_dir, &args).await?;
+            vec![generated_item(
+                format!("git {}", args.join(" ")),
+                "text",
+                log,
+            )]
+        }
+        BundleSource::Command {
+            command,
+            working_dir,
+            timeout_secs,
+            ttl_secs,
+        } => {
+            let working_dir = match working_dir {
+                Some(working_dir) => dir.join(working_dir),
+                None => src_dir.to_path_buf(),
+            };
+            // Keyed by the command and its dir, so a changed command does not reuse the old output
+            let cache_key = Sha256::digest(format!("{command}\n{}", working_dir.display()));
+            let cache_key: String = cache_key
+                .iter()
+                .take(CACHE_KEY_BYTES)
+                .map(|byte| format!("{byte:02x}"))
+                .collect();
+            let cache_file = cache_dir.join(format!("{}-{cache_key}.out", bundle.bundle_name));
+
+            let output = match cached_output(&cache_file, *ttl_secs) {
+                Some(output) => {
+                    debug!("Command '{command}' Output Reused (TTL)");
+                    output
+                }
+                None => {
+                    let output = run_command(command, &working_dir, *timeout_secs).await?;
+                    fs::write(&cache_file, &output)?;
+                    output
+                }
+            };
+            vec![generated_item(command.clone(), "text", output)]
+        }
+    };
+
+    Ok(items)
+}
+
+// * Runs the command with the shell in `working_dir`, and returns its output
+// - The command is killed after `timeout_secs` (with the processes it started, on unix)
+async fn run_command(command: &str, working_dir: &Path, timeout_secs: u64) -> Result<String> {
+    let (shell, shell_arg) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let mut shell_command = std::process::Command::new(shell);
+    shell_command
+        .arg(shell_arg)
+        .arg(command)
+        .current_dir(working_dir);
+    // In its own process group, so the whole group is killed on timeout (not only the shell)
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut shell_command, 0);
+
+    let child = Command::from(shell_command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|err| format!("Cannot run the command '{command}': {err}"))?;
+    let pid = child.id();
+
+    let output = match timeout(Duration::from_secs(timeout_secs), child.wait_with_output()).await {
+        Ok(output) => output.map_err(|err| format!("Cannot run the command '{command}': {err}"))?,
+        Err(_) => {
+            if let Some(pid) = pid {
+                kill_process_group(pid);
+            }
+            return Err(format!("Command '{command}' timed out after {timeout_secs}s").into());
+        }
+    };
+
+    if !output.status.success() {
+        return Err(format!(
+            "Command '{command}' failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// * Kills the process group of the command (the shell and the processes it started)
+// ! NOTE: With the `kill` command, as the unsafe code (e.g., `libc::killpg`) is forbidden
+#[cfg(unix)]
+fn kill_process_group(pid: u32) {
+    let _ = std::process::Command::new("kill")
+        .args(["-KILL", "--", &format!("-{pid}")])
+        .stderr(Stdio::null())
+        .status();
+}
+
+// - Without process groups, only the shell is killed (by `kill_on_drop`)
+#[cfg(not(unix))]
+fn kill_process_group(_pid: u32) {}
+
+// * Returns the cached output, if younger than `ttl_secs`
+fn cached_output(cache_file: &Path, ttl_secs: Option<u64>) -> Option<String> {
+    let ttl = Duration::from_secs(ttl_secs?);
+    let age = fs::metadata(cache_file)
+        .ok()?
+        .modified()
+        .ok()?
+        .elapsed()
+        .ok()?;
+    if age > ttl {
+        return None;
+    }
+
+    fs::read_to_string(cache_file).ok()
+}
+
+// * Returns the output as a bundle item, named by its command (e.g., `git diff --staged`)
+// - ! NOTE: An empty output is still bundled, so the previous one is replaced
+fn generated_item(path: String, language: &str, output: String) -> BundleItem {
+    let content = if output.trim().is_empty() {
+        "(No output)".to_string()
+    } else {
+        output
+    };
+
+    BundleItem::Generated {
+        path,
+        language: language.to_string(),
+        content,
+    }
+}
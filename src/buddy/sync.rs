@@ -0,0 +1,183 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tracing::debug;
+
+use crate::{
+    ais::assistant::FileId,
+    utils::{
+        bundle::SkippedFile,
+        files::{load_from_json, save_to_json},
+    },
+    Result,
+};
+
+// TODO: Define the CONSTANTS in a separate file constants.rs
+const SYNC_JSON: &str = "sync.json";
+
+// Held while syncing, so the syncs of the process do not overwrite `sync.json` of each other
+// (e.g., the watch mode and a `/rf` of the REPL, each with its own buddy)
+pub(super) static SYNC_LOCK: Mutex<()> = Mutex::const_new(());
+
+// * Content hash and remote file of each uploaded bundle part, saved in `.buddy/sync.json`
+// - A part is uploaded again only when its hash changed, or when its remote file is gone
+// - For the `per_file` bundles, each uploaded file is a part
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(super) struct SyncManifest {
+    // By bundle file name (which includes the assistant id)
+    pub bundles: HashMap<String, SyncEntry>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(super) struct SyncEntry {
+    // One part, unless the bundle is larger than its max size
+    pub parts: Vec<SyncPart>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct SyncPart {
+    // Part file name, or file path relative to the bundle dir for the `per_file` bundles
+    pub file_name: String,
+    // SHA-256 of the part file content
+    pub hash: String,
+    pub file_id: String,
+    // Unix timestamp (in seconds)
+    pub synced_at: i64,
+}
+
+// * State of a sync, shared by its bundles
+pub(super) struct SyncState {
+    pub manifest: SyncManifest,
+    pub data_dir: PathBuf,
+    // File Id by File Name, of the files attached to the Assistant
+    pub remote_files: HashMap<String, FileId>,
+    pub remote_file_ids: HashSet<String>,
+    // Upload all the files, even the unchanged ones
+    pub recreate: bool,
+    // Manifest entries of the synced bundles
+    pub entry_names: HashSet<String>,
+    pub skipped_files: Vec<SkippedFile>,
+    // `(bundle_name, error)` of the bundles whose source failed (their remote files are kept)
+    pub failed_bundles: Vec<(String, String)>,
+    pub num_uploaded: u32,
+}
+
+impl SyncState {
+    pub fn new(
+        data_dir: PathBuf,
+        remote_files: HashMap<String, FileId>,
+        recreate: bool,
+    ) -> Result<Self> {
+        let remote_file_ids = remote_files
+            .values()
+            .map(|file_id| file_id.to_string())
+            .collect();
+
+        Ok(Self {
+            manifest: SyncManifest::load(&data_dir)?,
+            data_dir,
+            remote_files,
+            remote_file_ids,
+            recreate,
+            entry_names: HashSet::new(),
+            skipped_files: Vec::new(),
+            failed_bundles: Vec::new(),
+            num_uploaded: 0,
+        })
+    }
+}
+
+impl SyncManifest {
+    // * Loads the manifest, or an empty one if nothing was synced yet
+    // - A manifest of an older format is ignored (the bundles are uploaded again)
+    pub fn load(data_dir: &Path) -> Result<Self> {
+        let file = data_dir.join(SYNC_JSON);
+        if !file.exists() {
+            return Ok(Self::default());
+        }
+
+        match load_from_json(file) {
+            Ok(manifest) => Ok(manifest),
+            Err(err) => {
+                debug!("Sync manifest ignored - {err}");
+                Ok(Self::default())
+            }
+        }
+    }
+
+    pub fn save(&self, data_dir: &Path) -> Result<()> {
+        save_to_json(data_dir.join(SYNC_JSON), self)
+    }
+
+    // * Returns `true` if the remote file of the bundle part has the same content
+    pub fn is_synced(
+        &self,
+        bundle_file_name: &str,
+        part_file_name: &str,
+        hash: &str,
+        remote_file_ids: &HashSet<String>,
+    ) -> bool {
+        self.bundles
+            .get(bundle_file_name)
+            .and_then(|entry| {
+                entry
+                    .parts
+                    .iter()
+                    .find(|part| part.file_name == part_file_name)
+            })
+            .map(|part| part.hash == hash && remote_file_ids.contains(&part.file_id))
+            .unwrap_or(false)
+    }
+
+    pub fn parts(&self, bundle_file_name: &str) -> &[SyncPart] {
+        self.bundles
+            .get(bundle_file_name)
+            .map(|entry| entry.parts.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub fn set_part(
+        &mut self,
+        bundle_file_name: &str,
+        part_file_name: &str,
+        hash: String,
+        file_id: &FileId,
+    ) {
+        let entry = self
+            .bundles
+            .entry(bundle_file_name.to_string())
+            .or_default();
+        entry.parts.retain(|part| part.file_name != part_file_name);
+        entry.parts.push(SyncPart {
+            file_name: part_file_name.to_string(),
+            hash,
+            file_id: file_id.to_string(),
+            synced_at: Utc::now().timestamp(),
+        });
+    }
+
+    // * Keeps only the parts still produced by the bundle, in their order
+    pub fn retain_parts(&mut self, bundle_file_name: &str, part_file_names: &[String]) {
+        if let Some(entry) = self.bundles.get_mut(bundle_file_name) {
+            entry
+                .parts
+                .retain(|part| part_file_names.contains(&part.file_name));
+            entry.parts.sort_by_key(|part| {
+                part_file_names
+                    .iter()
+                    .position(|file_name| *file_name == part.file_name)
+            });
+        }
+    }
+
+    // * Removes the entries of the bundles not synced anymore (e.g., of an old assistant)
+    pub fn retain(&mut self, bundle_file_names: &HashSet<String>) {
+        self.bundles
+            .retain(|bundle_file_name, _| bundle_file_names.contains(bundle_file_name));
+    }
+}
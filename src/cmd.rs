@@ -0,0 +1,212 @@
+use std::path::PathBuf;
+
+use clap::ValueEnum;
+
+use crate::{export::ExportFormat, Result};
+
+// Types
+// * Input Command from the User
+#[derive(Debug)]
+pub enum Cmd {
+    Quit,
+    Chat(String),
+    RefreshAll,
+    RefreshConversation,
+    RefreshInstructions,
+    RefreshFiles,
+    CodeList,
+    CodeSave {
+        index: usize,
+        path: PathBuf,
+    },
+    CodeCopy {
+        index: usize,
+    },
+    Export {
+        format: ExportFormat,
+        path: Option<PathBuf>,
+    },
+    History(Option<usize>),
+    NewConversation(Option<String>),
+    SwitchConversation(String),
+    ListConversations,
+    RenameConversation {
+        // `None` for the current conversation
+        name: Option<String>,
+        new_name: String,
+    },
+    // Number of messages to keep, or `-k` to keep up to the k-th newest message
+    // (the `#-k` of `/history`), `None` for all
+    Fork(Option<isize>),
+    Retry,
+    Undo,
+    Usage,
+    // Steps of the last run (retrievals, tool calls and message creation)
+    Steps,
+}
+
+// * Slash Commands known by the REPL, used for the Tab completion
+// - `(name, takes_path)`, where `takes_path` is `true` when the command arguments are file paths
+pub const CMD_SPECS: &[(&str, bool)] = &[
+    ("/q", false),
+    ("/r", false),
+    ("/ra", false),
+    ("/rc", false),
+    ("/ri", false),
+    ("/rf", false),
+    ("/code", true),
+    ("/export", true),
+    ("/history", false),
+    ("/new", false),
+    ("/switch", false),
+    ("/list", false),
+    ("/rename", false),
+    ("/fork", false),
+    ("/retry", false),
+    ("/undo", false),
+    ("/usage", false),
+    ("/steps", false),
+];
+
+impl Cmd {
+    pub fn from_input(input: impl Into<String>) -> Result<Self> {
+        let input = input.into();
+
+        let cmd = if input == "/q" {
+            Self::Quit
+        } else if input == "/r" || input == "/ra" {
+            Self::RefreshAll
+        } else if input == "/ri" {
+            Self::RefreshInstructions
+        } else if input == "/rf" {
+            Self::RefreshFiles
+        } else if input == "/rc" {
+            Self::RefreshConversation
+        } else if let Some(args) = cmd_args(&input, "/code") {
+            Self::from_code_args(args)?
+        } else if let Some(args) = cmd_args(&input, "/export") {
+            Self::from_export_args(args)?
+        } else if let Some(args) = cmd_args(&input, "/history") {
+            let count = Some(args).filter(|args| !args.is_empty());
+            Self::History(count.map(parse_count).transpose()?)
+        } else if let Some(args) = cmd_args(&input, "/new") {
+            let name = Some(args).filter(|args| !args.is_empty());
+            Self::NewConversation(name.map(parse_name).transpose()?)
+        } else if let Some(args) = cmd_args(&input, "/switch") {
+            Self::SwitchConversation(parse_name(args)?)
+        } else if input == "/retry" {
+            Self::Retry
+        } else if input == "/undo" {
+            Self::Undo
+        } else if input == "/usage" {
+            Self::Usage
+        } else if input == "/steps" {
+            Self::Steps
+        } else if input == "/list" {
+            Self::ListConversations
+        } else if let Some(args) = cmd_args(&input, "/rename") {
+            Self::from_rename_args(args)?
+        } else if let Some(args) = cmd_args(&input, "/fork") {
+            let index = Some(args).filter(|args| !args.is_empty());
+            Self::Fork(index.map(parse_fork_index).transpose()?)
+        } else {
+            Self::Chat(input)
+        };
+
+        Ok(cmd)
+    }
+
+    // * `/code`, `/code save <n> <path>` or `/code copy <n>`
+    fn from_code_args(args: &str) -> Result<Self> {
+        let mut args = args.splitn(3, ' ').filter(|arg| !arg.is_empty());
+
+        let cmd = match (args.next(), args.next(), args.next()) {
+            (None, _, _) => Self::CodeList,
+            (Some("save"), Some(index), Some(path)) => Self::CodeSave {
+                index: parse_index(index)?,
+                path: PathBuf::from(path.trim()),
+            },
+            (Some("copy"), Some(index), None) => Self::CodeCopy {
+                index: parse_index(index)?,
+            },
+            _ => return Err("Usage: /code | /code save <n> <path> | /code copy <n>".into()),
+        };
+
+        Ok(cmd)
+    }
+
+    // * `/export <format> [path]`
+    fn from_export_args(args: &str) -> Result<Self> {
+        let (format, path) = args.split_once(' ').unwrap_or((args, ""));
+        if format.is_empty() {
+            return Err("Usage: /export <markdown|html|json> [path]".into());
+        }
+
+        let format = ExportFormat::from_str(format, true).map_err(|_| {
+            format!("Unknown export format '{format}', expected markdown, html or json")
+        })?;
+        let path = Some(path.trim())
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from);
+
+        Ok(Self::Export { format, path })
+    }
+
+    // * `/rename <new_name>` or `/rename <name> <new_name>`
+    fn from_rename_args(args: &str) -> Result<Self> {
+        let args: Vec<&str> = args.split_whitespace().collect();
+
+        let cmd = match args.as_slice() {
+            [new_name] => Self::RenameConversation {
+                name: None,
+                new_name: parse_name(new_name)?,
+            },
+            [name, new_name] => Self::RenameConversation {
+                name: Some(name.to_string()),
+                new_name: parse_name(new_name)?,
+            },
+            _ => return Err("Usage: /rename <new_name> | /rename <name> <new_name>".into()),
+        };
+
+        Ok(cmd)
+    }
+}
+
+// * Returns the arguments of the command `name`, if the input is this command
+fn cmd_args<'a>(input: &'a str, name: &str) -> Option<&'a str> {
+    let args = input.strip_prefix(name)?;
+    if args.is_empty() || args.starts_with(' ') {
+        Some(args.trim())
+    } else {
+        None
+    }
+}
+
+fn parse_index(index: &str) -> Result<usize> {
+    index
+        .parse()
+        .map_err(|_| format!("Invalid index '{index}', expected a number").into())
+}
+
+fn parse_fork_index(index: &str) -> Result<isize> {
+    match index.parse() {
+        Ok(index) if index != 0 => Ok(index),
+        _ => Err(format!("Invalid index '{index}', expected a number like 3 or -2").into()),
+    }
+}
+
+fn parse_name(name: &str) -> Result<String> {
+    let name = name.trim();
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return Err(format!("Invalid conversation name '{name}', expected a single word").into());
+    }
+
+    Ok(name.to_string())
+}
+
+fn parse_count(count: &str) -> Result<usize> {
+    match count.parse() {
+        Ok(count) if count > 0 => Ok(count),
+        _ => Err(format!("Invalid count '{count}', expected a positive number").into()),
+    }
+}
@@ -0,0 +1,115 @@
+use std::{fs, path::Path};
+
+use console::style;
+
+use crate::{
+    utils::{
+        cli::{confirm, icon_check, print_diff},
+        files::ensure_dir,
+        markdown::{extract_code_blocks, CodeBlock},
+    },
+    Result,
+};
+
+// Code Blocks Commands
+// * Lists the code blocks of the last answer, with their index and language
+pub fn list_code_blocks(last_res: Option<&str>) -> Result<()> {
+    let code_blocks = get_code_blocks(last_res)?;
+
+    for (idx, code_block) in code_blocks.iter().enumerate() {
+        let lang = if code_block.lang.is_empty() {
+            "text"
+        } else {
+            &code_block.lang
+        };
+        let first_line = code_block.content.lines().next().unwrap_or("");
+        println!(
+            "{} {} {} lines - {}",
+            style(format!("[{}]", idx + 1)).color256(45),
+            style(lang).bold(),
+            code_block.content.lines().count(),
+            style(first_line).dim()
+        );
+    }
+
+    Ok(())
+}
+
+// * Writes the code block `index` (1 based) to `path`
+// - If the file exists, shows the diff and asks for confirmation before overwriting
+pub fn save_code_block(last_res: Option<&str>, index: usize, path: &Path) -> Result<()> {
+    let code_block = get_code_block(last_res, index)?;
+
+    if path.is_file() {
+        let old_content = fs::read_to_string(path)?;
+        if old_content == code_block.content {
+            println!(
+                "{} '{}' is already up to date",
+                icon_check(),
+                path.display()
+            );
+            return Ok(());
+        }
+        print_diff(&old_content, &code_block.content);
+        if !confirm(&format!("Overwrite '{}'?", path.display()))? {
+            return Ok(());
+        }
+    }
+
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        ensure_dir(parent)?;
+    }
+    fs::write(path, &code_block.content)?;
+    println!(
+        "{} Code Block {index} Saved - '{}'",
+        icon_check(),
+        path.display()
+    );
+
+    Ok(())
+}
+
+// * Writes the code block `index` (1 based) to a new temp file, and prints its path
+pub fn copy_code_block(last_res: Option<&str>, index: usize) -> Result<()> {
+    let code_block = get_code_block(last_res, index)?;
+
+    let temp_file = tempfile::Builder::new()
+        .prefix("buddy-code-")
+        .suffix(&format!(".{}", code_block.extension()))
+        .tempfile()?;
+    fs::write(temp_file.path(), &code_block.content)?;
+    let (_, path) = temp_file.keep()?;
+
+    println!(
+        "{} Code Block {index} Copied - '{}'",
+        icon_check(),
+        path.display()
+    );
+
+    Ok(())
+}
+
+fn get_code_blocks(last_res: Option<&str>) -> Result<Vec<CodeBlock>> {
+    let last_res = last_res.ok_or("No answer yet in this session")?;
+
+    let code_blocks = extract_code_blocks(last_res);
+    if code_blocks.is_empty() {
+        return Err("No code block in the last answer".into());
+    }
+
+    Ok(code_blocks)
+}
+
+fn get_code_block(last_res: Option<&str>, index: usize) -> Result<CodeBlock> {
+    let mut code_blocks = get_code_blocks(last_res)?;
+    let num_blocks = code_blocks.len();
+
+    if index == 0 || index > num_blocks {
+        return Err(format!("Invalid code block {index}, expected 1 to {num_blocks}").into());
+    }
+
+    Ok(code_blocks.swap_remove(index - 1))
+}
@@ -0,0 +1,207 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Local};
+use clap::ValueEnum;
+use pulldown_cmark::{html::push_html, Event, Options, Parser};
+use serde::Serialize;
+
+use crate::{
+    ais::msg::{Citation, ThreadMsg},
+    Result,
+};
+
+// Types
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ExportFormat {
+    #[value(alias = "md")]
+    Markdown,
+    Html,
+    Json,
+}
+
+#[derive(Serialize)]
+struct Transcript<'a> {
+    buddy: &'a str,
+    thread_id: &'a str,
+    exported_at: String,
+    messages: &'a [ThreadMsg],
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Html => "html",
+            Self::Json => "json",
+        }
+    }
+}
+
+// Export
+// * Writes the transcript of the conversation, and returns the written file
+// - When no `path` is given, the file is `{buddy}-{thread_id}.{ext}` in the current dir
+pub fn export_conversation(
+    buddy_name: &str,
+    thread_id: &str,
+    msgs: &[ThreadMsg],
+    format: ExportFormat,
+    path: Option<&Path>,
+) -> Result<PathBuf> {
+    let transcript = Transcript {
+        buddy: buddy_name,
+        thread_id,
+        exported_at: Local::now().to_rfc3339(),
+        messages: msgs,
+    };
+
+    let content = match format {
+        ExportFormat::Markdown => to_markdown(&transcript),
+        ExportFormat::Html => to_html(&transcript),
+        ExportFormat::Json => serde_json::to_string_pretty(&transcript)?,
+    };
+
+    let path = match path {
+        Some(path) => path.to_path_buf(),
+        None => PathBuf::from(format!("{buddy_name}-{thread_id}.{}", format.extension())),
+    };
+    fs::write(&path, content)
+        .map_err(|err| format!("Cannot Write File '{}': {}", path.display(), err))?;
+
+    Ok(path)
+}
+
+// Formats
+fn to_markdown(transcript: &Transcript) -> String {
+    let mut md = format!(
+        "# Conversation with {}\n\n- Thread: `{}`\n- Exported: {}\n",
+        transcript.buddy,
+        transcript.thread_id,
+        format_date(transcript.exported_at.as_str())
+    );
+
+    for msg in transcript.messages {
+        md.push_str(&format!(
+            "\n---\n\n## {} - {}\n\n{}\n",
+            msg.role_title(),
+            format_timestamp(msg.created_at),
+            msg.text.trim_end()
+        ));
+
+        if !msg.citations.is_empty() {
+            md.push_str("\n**Citations**\n\n");
+            for citation in msg.citations.iter() {
+                md.push_str(&format!(
+                    "- `{}` {}",
+                    citation.marker,
+                    citation_source(citation)
+                ));
+                if let Some(quote) = citation.quote.as_ref() {
+                    md.push_str(&format!("\n  > {}", quote.replace('\n', "\n  > ")));
+                }
+                md.push('\n');
+            }
+        }
+    }
+
+    md
+}
+
+fn to_html(transcript: &Transcript) -> String {
+    let mut body = String::new();
+
+    for msg in transcript.messages {
+        body.push_str(&format!(
+            "<section class=\"msg {}\">\n<h2>{} <small>{}</small></h2>\n",
+            escape_html(&msg.role),
+            msg.role_title(),
+            format_timestamp(msg.created_at)
+        ));
+
+        // Raw HTML from the messages is shown as text
+        let events = Parser::new_ext(&msg.text, Options::ENABLE_TABLES).map(|event| match event {
+            Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+            event => event,
+        });
+        push_html(&mut body, events);
+
+        if !msg.citations.is_empty() {
+            body.push_str("<h3>Citations</h3>\n<ul class=\"citations\">\n");
+            for citation in msg.citations.iter() {
+                body.push_str(&format!(
+                    "<li><code>{}</code> {}",
+                    escape_html(&citation.marker),
+                    escape_html(&citation_source(citation))
+                ));
+                if let Some(quote) = citation.quote.as_ref() {
+                    body.push_str(&format!("<blockquote>{}</blockquote>", escape_html(quote)));
+                }
+                body.push_str("</li>\n");
+            }
+            body.push_str("</ul>\n");
+        }
+
+        body.push_str("</section>\n");
+    }
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>Conversation with {buddy}</title>
+<style>
+body {{ font-family: sans-serif; max-width: 50rem; margin: 2rem auto; padding: 0 1rem; line-height: 1.5; }}
+.msg {{ border-top: 1px solid #ddd; padding: 0.5rem 0; }}
+.msg.user h2 {{ color: #0087af; }}
+h2 small {{ color: #888; font-weight: normal; font-size: 0.7em; }}
+pre {{ background: #f4f4f4; padding: 0.75rem; overflow-x: auto; }}
+table {{ border-collapse: collapse; }}
+td, th {{ border: 1px solid #ddd; padding: 0.25rem 0.5rem; }}
+blockquote {{ color: #555; border-left: 3px solid #ddd; margin: 0.25rem 0; padding-left: 0.75rem; }}
+</style>
+</head>
+<body>
+<h1>Conversation with {buddy}</h1>
+<p>Thread: <code>{thread_id}</code><br>Exported: {exported_at}</p>
+{body}</body>
+</html>
+"#,
+        buddy = escape_html(transcript.buddy),
+        thread_id = escape_html(transcript.thread_id),
+        exported_at = format_date(&transcript.exported_at),
+    )
+}
+
+// Helpers
+fn citation_source(citation: &Citation) -> String {
+    match citation.file_name.as_ref() {
+        Some(file_name) => format!("{} ({})", file_name, citation.file_id),
+        None => citation.file_id.clone(),
+    }
+}
+
+pub fn format_timestamp(timestamp: i64) -> String {
+    DateTime::from_timestamp(timestamp, 0)
+        .map(|date| {
+            date.with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string()
+        })
+        .unwrap_or_default()
+}
+
+fn format_date(rfc3339: &str) -> String {
+    DateTime::parse_from_rfc3339(rfc3339)
+        .map(|date| date.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_default()
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
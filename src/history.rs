@@ -0,0 +1,164 @@
+use console::style;
+
+use crate::{
+    ais::{
+        msg::ThreadMsg,
+        steps::{RunStep, RunStepDetails},
+    },
+    buddy::{Buddy, Conversation},
+    export::format_timestamp,
+    utils::cli::{confirm, print_res},
+    Result,
+};
+
+// TODO: Define the CONSTANTS in a separate file constants.rs
+pub const DEFAULT_HISTORY_PAGE_SIZE: usize = 10;
+// Max chars of the tool call inputs and outputs shown by `/steps`
+const STEP_DETAIL_MAX_CHARS: usize = 300;
+
+// History
+// * Pages through the previous messages of the conversation, from the most recent ones
+// - Each page shows `page_size` messages, and asks before showing the older ones
+// - The pages are fetched from the API when shown, so long threads are not fetched whole
+// - Messages are shown with their index from the newest one (e.g., `#-3`, for `/fork -3`)
+pub async fn show_history(
+    buddy: &Buddy,
+    conversation: &Conversation,
+    page_size: usize,
+    plain: bool,
+) -> Result<()> {
+    let mut after: Option<String> = None;
+    let mut num_shown = 0;
+
+    loop {
+        let (mut msgs, has_more) = buddy
+            .conversation_msgs_page(conversation, page_size, after.as_deref())
+            .await?;
+
+        if msgs.is_empty() && after.is_none() {
+            println!("{}", style("No messages in this conversation yet").dim());
+            return Ok(());
+        }
+
+        after = msgs.last().map(|msg| msg.id.clone());
+        let num_msgs = msgs.len();
+        msgs.reverse();
+        for (idx, msg) in msgs.iter().enumerate() {
+            let index = -((num_shown + num_msgs - idx) as isize);
+            print_msg(msg, Some(index), plain);
+        }
+        num_shown += num_msgs;
+
+        if !has_more || !confirm("Show older messages?")? {
+            return Ok(());
+        }
+        println!("\n{}", style("──── Older Messages ────").dim());
+    }
+}
+
+// * Shows the last `num_exchanges` user/assistant exchanges, without paging
+pub async fn show_last_exchanges(
+    buddy: &Buddy,
+    conversation: &Conversation,
+    num_exchanges: usize,
+    plain: bool,
+) -> Result<()> {
+    let (mut msgs, _) = buddy
+        .conversation_msgs_page(conversation, num_exchanges * 2, None)
+        .await?;
+
+    msgs.reverse();
+    for msg in msgs.iter() {
+        print_msg(msg, None, plain);
+    }
+
+    Ok(())
+}
+
+fn print_msg(msg: &ThreadMsg, index: Option<isize>, plain: bool) {
+    let index = index.map(|index| format!("#{index} ")).unwrap_or_default();
+    println!(
+        "\n{}{} {}",
+        style(index).color256(45),
+        style(msg.role_title()).bold(),
+        style(format_timestamp(msg.created_at)).dim()
+    );
+
+    if msg.role == "assistant" {
+        print_res(&msg.text, plain);
+    } else {
+        println!("{}", msg.text);
+    }
+}
+
+// Run Steps
+// * Prints the steps of the last run of the conversation, oldest first
+pub async fn show_run_steps(buddy: &Buddy, conversation: &Conversation) -> Result<()> {
+    let steps = buddy.last_run_steps(conversation).await?;
+    if steps.is_empty() {
+        println!("{}", style("No steps in the last run").dim());
+    }
+
+    for (idx, step) in steps.iter().enumerate() {
+        print_run_step(step, idx + 1);
+    }
+
+    Ok(())
+}
+
+fn print_run_step(step: &RunStep, index: usize) {
+    let kind = match &step.details {
+        RunStepDetails::MessageCreation { .. } => "Message creation",
+        RunStepDetails::ToolCalls(_) => "Tool calls",
+    };
+    let duration = step
+        .duration_secs()
+        .map(|secs| format!("{secs}s"))
+        .unwrap_or_else(|| "-".to_string());
+    println!(
+        "\n{} {} {}",
+        style(format!("#{index}")).color256(45),
+        style(kind).bold(),
+        style(format!(
+            "- {} - {} - started {} - {}",
+            step.status,
+            duration,
+            format_timestamp(step.created_at),
+            step.id
+        ))
+        .dim()
+    );
+
+    match &step.details {
+        RunStepDetails::MessageCreation { message_id } => {
+            println!("  {} {message_id}", style("message").dim());
+        }
+        RunStepDetails::ToolCalls(tool_calls) => {
+            for tool_call in tool_calls {
+                println!("  {} {}", style("tool").dim(), tool_call.tool);
+                if let Some(input) = tool_call.input.as_deref() {
+                    println!("    {} {}", style("input").dim(), truncate_detail(input));
+                }
+                if let Some(output) = tool_call.output.as_deref() {
+                    println!("    {} {}", style("output").dim(), truncate_detail(output));
+                }
+                if tool_call.input.is_none() && tool_call.output.is_none() {
+                    println!(
+                        "    {}",
+                        style("(no input or output reported by the API)").dim()
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn truncate_detail(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() > STEP_DETAIL_MAX_CHARS {
+        let text: String = text.chars().take(STEP_DETAIL_MAX_CHARS).collect();
+        format!("{text}...")
+    } else {
+        text
+    }
+}
@@ -0,0 +1,259 @@
+use std::{
+    fmt,
+    io::{self, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+use tracing::{Event, Level, Subscriber};
+use tracing_appender::{
+    non_blocking::{NonBlocking, WorkerGuard},
+    rolling::{Builder, Rotation},
+};
+use tracing_subscriber::{
+    fmt::{format, FmtContext, FormatEvent, FormatFields},
+    layer::SubscriberExt,
+    registry::LookupSpan,
+    util::SubscriberInitExt,
+    EnvFilter, Layer,
+};
+
+use crate::{
+    utils::cli::{icon_err, icon_warn},
+    Result,
+};
+
+// TODO: Define the CONSTANTS in a separate file constants.rs
+// Target of the API request and response summaries (see `ais::api_call`)
+pub const API_LOG_TARGET: &str = "api";
+const LOG_FILE_PREFIX: &str = "buddy";
+const LOG_FILE_SUFFIX: &str = "log";
+const MAX_LOG_FILES: usize = 7;
+const REDACTED: &str = "***";
+// Min length of a `sk-...` key to be redacted, to not redact words like `sk-learn`
+const SECRET_KEY_MIN_CHARS: usize = 16;
+
+// Console lines captured while the TUI owns the terminal (`None` when not capturing)
+static CAPTURED_LOGS: Mutex<Option<Vec<String>>> = Mutex::new(None);
+
+// Printer of the console lines while the REPL prompt is shown (so they do not break the prompt line)
+static CONSOLE_PRINTER: Mutex<Option<ConsolePrinter>> = Mutex::new(None);
+
+// Types
+pub type ConsolePrinter = Box<dyn FnMut(String) + Send>;
+
+pub struct LogOptions {
+    // `-1` for `--quiet`, `0` by default, and `1+` for each `-v`
+    pub verbosity: i8,
+    // Logs the API request and response summaries in the log file
+    pub log_api: bool,
+}
+
+// * Prints the message only, with the warning and error icons (The other icons are in the messages)
+struct ConsoleFormat;
+
+// * Writes the console events to stderr, or in `CAPTURED_LOGS` while capturing,
+//   or to the `CONSOLE_PRINTER` while it is set, with the secrets redacted
+struct ConsoleWriter {
+    secrets: Vec<String>,
+}
+
+// * Writes the events in the log file, without the styles and with the secrets redacted
+struct RedactWriter {
+    inner: NonBlocking,
+    secrets: Vec<String>,
+}
+
+// Setup
+// * Logs to the console (stderr, filtered by the verbosity) and to a daily rotated file in `logs_dir`
+// - Without `logs_dir` (e.g., no buddy dir yet), logs to the console only
+// - The returned guard must be kept until the end, as it flushes the log file when dropped
+pub fn init_logging(logs_dir: Option<&Path>, options: &LogOptions) -> Result<Option<WorkerGuard>> {
+    let console_filter = match options.verbosity {
+        i8::MIN..=-1 => "ai_buddy=warn",
+        0 => "ai_buddy=info",
+        1 => "ai_buddy=debug",
+        _ => "ai_buddy=trace,api=debug", // Same as `API_LOG_TARGET`
+    };
+    let file_filter = if options.log_api {
+        format!("ai_buddy=debug,{API_LOG_TARGET}=debug,reqwest=debug")
+    } else {
+        "ai_buddy=debug".to_string()
+    };
+
+    // The API key is redacted wherever it appears, not only in the known places
+    let secrets: Vec<String> = dotenv::var("OPENAI_API_KEY")
+        .into_iter()
+        .filter(|key| !key.is_empty())
+        .collect();
+
+    let console_secrets = secrets.clone();
+    let console_layer = tracing_subscriber::fmt::layer()
+        .event_format(ConsoleFormat)
+        .with_writer(move || ConsoleWriter {
+            secrets: console_secrets.clone(),
+        })
+        .with_filter(EnvFilter::new(console_filter));
+
+    let (file_layer, guard) = match logs_dir {
+        Some(logs_dir) => {
+            let file_appender = Builder::new()
+                .rotation(Rotation::DAILY)
+                .filename_prefix(LOG_FILE_PREFIX)
+                .filename_suffix(LOG_FILE_SUFFIX)
+                .max_log_files(MAX_LOG_FILES)
+                .build(logs_dir)?;
+            let (non_blocking, guard) = tracing_appender::non_blocking(file_appender);
+
+            let file_layer = tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(move || RedactWriter {
+                    inner: non_blocking.clone(),
+                    secrets: secrets.clone(),
+                })
+                .with_filter(EnvFilter::new(file_filter));
+            (Some(file_layer), Some(guard))
+        }
+        None => (None, None),
+    };
+
+    tracing_subscriber::registry()
+        .with(console_layer)
+        .with(file_layer)
+        .try_init()?;
+
+    Ok(guard)
+}
+
+// Capture
+// * Captures the console logs instead of writing them to stderr (e.g., for the TUI)
+pub fn start_log_capture() {
+    if let Ok(mut captured) = CAPTURED_LOGS.lock() {
+        captured.get_or_insert_with(Vec::new);
+    }
+}
+
+// * Writes the console logs to stderr again
+pub fn stop_log_capture() {
+    if let Ok(mut captured) = CAPTURED_LOGS.lock() {
+        captured.take();
+    }
+}
+
+// * Returns the console lines captured since the last call
+pub fn take_captured_logs() -> Vec<String> {
+    CAPTURED_LOGS
+        .lock()
+        .ok()
+        .and_then(|mut captured| captured.as_mut().map(std::mem::take))
+        .unwrap_or_default()
+}
+
+// * Prints the console logs with the printer instead of writing them to stderr (e.g., while reading a line)
+// - `None` writes them to stderr again
+pub fn set_console_printer(printer: Option<ConsolePrinter>) {
+    if let Ok(mut console_printer) = CONSOLE_PRINTER.lock() {
+        *console_printer = printer;
+    }
+}
+
+// Redaction
+// * Replaces the secrets, the `Bearer` tokens and the `sk-...` keys by `***`
+fn redact_secrets(text: &str, secrets: &[String]) -> String {
+    let mut text = text.to_string();
+    for secret in secrets {
+        text = text.replace(secret.as_str(), REDACTED);
+    }
+
+    let text = redact_after(&text, "Bearer ", 1);
+    redact_after(&text, "sk-", SECRET_KEY_MIN_CHARS)
+}
+
+// * Redacts the token following each `prefix`, if it has at least `min_chars` chars
+fn redact_after(text: &str, prefix: &str, min_chars: usize) -> String {
+    let is_token_char = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.';
+
+    let mut redacted = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(idx) = rest.find(prefix) {
+        let (before, after) = rest.split_at(idx + prefix.len());
+        redacted.push_str(before);
+
+        let token_len = after.find(|c| !is_token_char(c)).unwrap_or(after.len());
+        if token_len >= min_chars {
+            redacted.push_str(REDACTED);
+        } else {
+            redacted.push_str(&after[..token_len]);
+        }
+        rest = &after[token_len..];
+    }
+    redacted.push_str(rest);
+
+    redacted
+}
+
+// Impls
+impl<S, N> FormatEvent<S, N> for ConsoleFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: format::Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        match *event.metadata().level() {
+            Level::ERROR => write!(writer, "{} ", icon_err())?,
+            Level::WARN => write!(writer, "{} ", icon_warn())?,
+            _ => (),
+        }
+        ctx.field_format().format_fields(writer.by_ref(), event)?;
+
+        writeln!(writer)
+    }
+}
+
+impl Write for ConsoleWriter {
+    // ! NOTE: Like `RedactWriter`, each event is written at once
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let text = redact_secrets(&String::from_utf8_lossy(buf), &self.secrets);
+
+        if let Ok(mut captured) = CAPTURED_LOGS.lock() {
+            if let Some(captured) = captured.as_mut() {
+                captured.extend(text.lines().map(str::to_string));
+                return Ok(buf.len());
+            }
+        }
+        if let Ok(mut printer) = CONSOLE_PRINTER.lock() {
+            if let Some(printer) = printer.as_mut() {
+                printer(text);
+                return Ok(buf.len());
+            }
+        }
+
+        io::stderr().write_all(text.as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+impl Write for RedactWriter {
+    // ! NOTE: The fmt layer writes each event at once, so a secret is never split between two writes
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let text = String::from_utf8_lossy(buf);
+        let text = console::strip_ansi_codes(&text);
+        self.inner
+            .write_all(redact_secrets(&text, &self.secrets).as_bytes())?;
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
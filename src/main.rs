@@ -1,21 +1,64 @@
 mod ais; // Lower level code to interface with the AIS
+mod args;
 mod buddy; // APP code to interface with the AIS
+mod cmd;
+mod code;
 mod error;
+mod export;
+mod history;
+mod logs;
+mod tui;
+mod usage;
 mod utils;
+mod watch;
 
-use textwrap::wrap;
+use std::{path::Path, thread};
+
+use clap::Parser;
+use console::style;
+use tokio::{runtime::Builder, sync::oneshot};
+use tracing::error;
 
 use crate::{
-    buddy::Buddy,
-    utils::cli::{icon_res, prompt, text_res},
+    args::{Args, Command},
+    buddy::{Buddy, Conversation},
+    cmd::{Cmd, CMD_SPECS},
+    code::{copy_code_block, list_code_blocks, save_code_block},
+    export::{export_conversation, format_timestamp, ExportFormat},
+    history::{show_history, show_last_exchanges, show_run_steps, DEFAULT_HISTORY_PAGE_SIZE},
+    logs::init_logging,
+    tui::run_tui,
+    usage::{format_totals, print_usage_summary},
+    utils::{
+        cli::{icon_check, print_res, with_status_line},
+        editor::LineEditor,
+    },
+    watch::watch_and_sync,
 };
 
 pub use self::error::{Error, Result};
 
 #[tokio::main]
 async fn main() {
-    match start().await {
+    let args = Args::parse();
+
+    dotenv::dotenv().expect("Failed to read .env file");
+
+    // ! NOTE: The guard flushes the log file when dropped, so it is kept until the end
+    let log_guard = match Buddy::logs_dir(DEFAULT_DIR)
+        .and_then(|logs_dir| init_logging(logs_dir.as_deref(), &args.log_options()))
+    {
+        Ok(guard) => guard,
+        Err(e) => {
+            eprintln!("Cannot setup the logging: {}", e);
+            None
+        }
+    };
+
+    match start(args).await {
         Ok(_) => println!("\nGoodbye!"),
+        // Logged when there is a log file (the console layer prints it too), printed otherwise
+        Err(e) if log_guard.is_some() => error!("Error: {}", e),
         Err(e) => eprintln!("Error: {}", e),
     }
 }
@@ -23,57 +66,70 @@ async fn main() {
 // TODO: Define the CONSTANTS in a separate file constants.rs
 const DEFAULT_DIR: &str = "buddy";
 
-// TODO: Separate the types in another module
-// Types
-// * Input Command from the User
-#[derive(Debug)]
-enum Cmd {
-    Quit,
-    Chat(String),
-    RefreshAll,
-    RefreshConversation,
-    RefreshInstructions,
-    RefreshFiles,
-}
-
-impl Cmd {
-    fn from_input(input: impl Into<String>) -> Self {
-        let input = input.into();
-
-        if input == "/q" {
-            Self::Quit
-        } else if input == "/r" || input == "/ra" {
-            Self::RefreshAll
-        } else if input == "/ri" {
-            Self::RefreshInstructions
-        } else if input == "/rf" {
-            Self::RefreshFiles
-        } else if input == "/rc" {
-            Self::RefreshConversation
-        } else {
-            Self::Chat(input)
+async fn start(args: Args) -> Result<()> {
+    match &args.command {
+        Some(Command::Export { format, path }) => {
+            let buddy = Buddy::load_from_dir(DEFAULT_DIR, false).await?;
+            // ! NOTE: Read-only, the conversation is not created (nor made the current one)
+            let conversation = buddy.find_conversation(args.conversation.as_deref())?;
+            export(&buddy, &conversation, *format, path.as_deref()).await
+        }
+        Some(Command::Usage) => {
+            print_usage_summary(&Buddy::load_usage_records(DEFAULT_DIR)?);
+            Ok(())
         }
+        Some(Command::Watch) => {
+            let buddy = Buddy::init_from_dir(DEFAULT_DIR, false).await?;
+            tokio::select! {
+                res = watch_and_sync(buddy) => res,
+                _ = tokio::signal::ctrl_c() => Ok(()),
+            }
+        }
+        None if args.tui => chat_tui(&args).await,
+        None => chat_repl(&args).await,
     }
 }
 
-async fn start() -> Result<()> {
-    dotenv::dotenv().expect("Failed to read .env file");
-
+async fn chat_repl(args: &Args) -> Result<()> {
     let mut buddy = Buddy::init_from_dir(DEFAULT_DIR, false).await?;
 
-    let mut conversation = buddy.load_or_create_conversation(false).await?;
+    let mut conversation = open_conversation(&buddy, args).await?;
+
+    if buddy.history_on_start() > 0 {
+        show_last_exchanges(&buddy, &conversation, buddy.history_on_start(), args.plain).await?;
+    }
+
+    let mut editor = LineEditor::new(&buddy.history_file()?, CMD_SPECS)?;
+
+    let watcher = args.watch.then(spawn_watcher);
+
+    let mut last_res: Option<String> = None;
 
     loop {
         println!(); // Add a blank line
-        let input = prompt("Prompt")?;
-        let cmd = Cmd::from_input(input);
+        let Some(input) = editor.prompt("Prompt")? else {
+            break;
+        };
+        if input.is_empty() {
+            continue;
+        }
+        let cmd = match Cmd::from_input(input) {
+            Ok(cmd) => cmd,
+            Err(err) => {
+                error!("{err}");
+                continue;
+            }
+        };
 
         match cmd {
             Cmd::Quit => break,
             Cmd::Chat(msg) => {
-                let res = buddy.chat(&conversation, &msg).await?;
-                let res = wrap(&res, 80).join("\n"); // TODO: The 80 could be in a Constant
-                println!("{} {}", icon_res(), text_res(res));
+                // ! NOTE: A refused run (e.g., budget exceeded) must not leave the REPL
+                let chat = buddy.chat(&mut conversation, &msg);
+                if let Some(res) = ok_or_print(with_status_line(buddy.run_progress(), chat).await) {
+                    print_res(&res, args.plain);
+                    last_res = Some(res);
+                }
             }
             Cmd::RefreshAll => {
                 buddy = Buddy::init_from_dir(DEFAULT_DIR, true).await?;
@@ -90,17 +146,198 @@ async fn start() -> Result<()> {
                 buddy.upload_files(true).await?;
                 conversation = buddy.load_or_create_conversation(true).await?;
             }
+            Cmd::CodeList => print_if_err(list_code_blocks(last_res.as_deref())),
+            Cmd::CodeSave { index, path } => {
+                print_if_err(save_code_block(last_res.as_deref(), index, &path))
+            }
+            Cmd::CodeCopy { index } => print_if_err(copy_code_block(last_res.as_deref(), index)),
+            Cmd::Export { format, path } => {
+                print_if_err(export(&buddy, &conversation, format, path.as_deref()).await)
+            }
+            Cmd::History(count) => {
+                let page_size = count.unwrap_or(DEFAULT_HISTORY_PAGE_SIZE);
+                print_if_err(show_history(&buddy, &conversation, page_size, args.plain).await)
+            }
+            Cmd::NewConversation(name) => {
+                if let Some(new) = ok_or_print(buddy.new_conversation(name.as_deref()).await) {
+                    conversation = new;
+                }
+            }
+            Cmd::SwitchConversation(name) => {
+                if let Some(switched) = ok_or_print(buddy.switch_conversation(&name, false).await) {
+                    conversation = switched;
+                }
+            }
+            Cmd::Fork(num_msgs) => {
+                if let Some(fork) =
+                    ok_or_print(buddy.fork_conversation(&conversation, num_msgs).await)
+                {
+                    conversation = fork;
+                }
+            }
+            Cmd::Retry => {
+                let retry = buddy.retry_last_exchange(&mut conversation);
+                if let Some(res) = ok_or_print(with_status_line(buddy.run_progress(), retry).await)
+                {
+                    print_res(&res, args.plain);
+                    last_res = Some(res);
+                }
+            }
+            Cmd::Undo => {
+                if let Some(msg) = ok_or_print(buddy.undo_last_exchange(&mut conversation).await) {
+                    println!("{} Last Exchange Removed - '{}'", icon_check(), msg);
+                    last_res = None;
+                }
+            }
+            Cmd::Usage => {
+                if let Some(records) = ok_or_print(buddy.usage_records()) {
+                    print_usage_summary(&records);
+                }
+            }
+            Cmd::Steps => print_if_err(show_run_steps(&buddy, &conversation).await),
+            Cmd::ListConversations => print_if_err(list_conversations(&buddy)),
+            Cmd::RenameConversation { name, new_name } => {
+                let name = name.unwrap_or_else(|| conversation.name.clone());
+                if let Some(renamed) = ok_or_print(buddy.rename_conversation(&name, &new_name)) {
+                    println!(
+                        "{} Conversation '{name}' Renamed to '{new_name}'",
+                        icon_check()
+                    );
+                    if conversation.name == name {
+                        conversation = renamed;
+                    }
+                }
+            }
         }
     }
 
+    // Stops the watch mode (if any)
+    drop(watcher);
+    print_session_end(&buddy, &conversation).await
+}
+
+async fn chat_tui(args: &Args) -> Result<()> {
+    let mut buddy = Buddy::init_from_dir(DEFAULT_DIR, false).await?;
+    let mut conversation = open_conversation(&buddy, args).await?;
+
+    let watcher = args.watch.then(spawn_watcher);
+    let res = run_tui(&mut buddy, &mut conversation, args.plain).await;
+    // Stops the watch mode (if any)
+    drop(watcher);
+    res?;
+
+    print_session_end(&buddy, &conversation).await
+}
+
+// * Runs the watch mode in the background, with its own buddy and runtime (the buddy futures are not `Send`)
+// - The watch mode stops when the returned sender is dropped
+// - Its notices are logged, so they are printed above the prompt (see `LineEditor::prompt`),
+//   over the status line (see `with_status_line`), or in the TUI logs panel
+fn spawn_watcher() -> oneshot::Sender<()> {
+    let (stop_tx, stop_rx) = oneshot::channel();
+    thread::spawn(move || {
+        let runtime = match Builder::new_current_thread().enable_all().build() {
+            Ok(runtime) => runtime,
+            Err(err) => return error!("Cannot start the watch mode - {err}"),
+        };
+        let res: Result<()> = runtime.block_on(async {
+            let buddy = Buddy::load_from_dir(DEFAULT_DIR, false).await?;
+            tokio::select! {
+                res = watch_and_sync(buddy) => res,
+                _ = stop_rx => Ok(()),
+            }
+        });
+        if let Err(err) = res {
+            error!("Watch mode stopped - {err}");
+        }
+    });
+
+    stop_tx
+}
+
+// * Waits for the background tasks, and prints the conversation and the session usage
+async fn print_session_end(buddy: &Buddy, conversation: &Conversation) -> Result<()> {
+    buddy.wait_background_tasks().await;
+    let conversation = buddy.reload_conversation(conversation)?;
+    println!(
+        "\n--> Buddy {} - Conversation {}",
+        buddy.name(),
+        conversation.display_name()
+    );
+    if let Some(summary) = conversation.summary.as_ref() {
+        println!("    {}", style(summary).dim());
+    }
+    let session_usage = buddy.session_usage();
+    if session_usage.runs > 0 {
+        println!("--> Session Usage - {}", format_totals(&session_usage));
+    }
+
+    Ok(())
+}
+
+// * Opens the conversation of the `--conversation` argument, or the current one
+async fn open_conversation(buddy: &Buddy, args: &Args) -> Result<Conversation> {
+    match args.conversation.as_deref() {
+        Some(name) => buddy.switch_conversation(name, true).await,
+        None => buddy.load_or_create_conversation(false).await,
+    }
+}
+
+fn list_conversations(buddy: &Buddy) -> Result<()> {
+    let (conversations, current) = buddy.list_conversations()?;
+
+    for conversation in conversations.iter() {
+        let is_current = current.as_deref() == Some(conversation.name.as_str());
+        let marker = if is_current { "*" } else { " " };
+        println!(
+            "{} {} {}",
+            style(marker).color256(45),
+            style(conversation.display_name()).bold(),
+            style(format!(
+                "- created {} - last used {}",
+                format_timestamp(conversation.created_at),
+                format_timestamp(conversation.last_used_at)
+            ))
+            .dim()
+        );
+        if let Some(summary) = conversation.summary.as_ref() {
+            println!("  {}", style(summary).dim());
+        }
+    }
+
+    Ok(())
+}
+
+async fn export(
+    buddy: &Buddy,
+    conversation: &Conversation,
+    format: ExportFormat,
+    path: Option<&Path>,
+) -> Result<()> {
+    let msgs = buddy.conversation_msgs(conversation).await?;
+    let file = export_conversation(buddy.name(), conversation, &msgs, format, path)?;
     println!(
-        "\n--> Buddy {} - Conversation {conversation:?}",
-        buddy.name()
+        "{} Conversation Exported - '{}' ({} messages)",
+        icon_check(),
+        file.display(),
+        msgs.len()
     );
 
     Ok(())
 }
 
+// * Prints the error of a REPL command without leaving the REPL
+fn print_if_err(res: Result<()>) {
+    if let Err(err) = res {
+        error!("{err}");
+    }
+}
+
+// * Returns the value of a REPL command, or prints its error without leaving the REPL
+fn ok_or_print<T>(res: Result<T>) -> Option<T> {
+    res.map_err(|err| error!("{err}")).ok()
+}
+
 // ! FOR TESTING ONLY
 // async fn start() -> Result<()> {
 //     dotenv::dotenv().expect("Failed to read .env file");
@@ -0,0 +1,296 @@
+use std::time::Instant;
+
+use ansi_to_tui::IntoText;
+use chrono::Utc;
+use console::style;
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::text::{Line, Text};
+use textwrap::wrap;
+use tokio::sync::watch;
+use tracing::error;
+use tui_textarea::TextArea;
+
+use super::synced_status;
+use crate::{
+    ais::{msg::ThreadMsg, steps::RunProgress},
+    buddy::{Buddy, Conversation},
+    cmd::Cmd,
+    export::format_timestamp,
+    logs::take_captured_logs,
+    utils::{
+        cli::{icon_res, spinner_frame},
+        markdown::render_markdown,
+    },
+};
+
+// TODO: Define the CONSTANTS in a separate file constants.rs
+const MAX_LOG_LINES: usize = 100;
+const SCROLL_PAGE_LINES: u16 = 10;
+pub(super) const INPUT_PLACEHOLDER: &str = "Message or /command";
+
+// Types
+// * State of the TUI, the buddy and the conversation are kept by the caller
+pub(super) struct App {
+    pub msgs: Vec<ThreadMsg>,
+    pub input: TextArea<'static>,
+    // Lines scrolled up from the bottom of the conversation
+    pub scroll: u16,
+    pub status: Option<Status>,
+    // Live status of the runs, shown instead of the status label while a run is in progress
+    run_progress: watch::Receiver<Option<RunProgress>>,
+    pub panel: PanelInfo,
+    pub sync_status: String,
+    pub logs: Vec<String>,
+    pub plain: bool,
+    pub should_quit: bool,
+    // Rendered conversation, for the (width, number of messages)
+    rendered: Option<(u16, usize, Text<'static>)>,
+}
+
+// * Task in progress (e.g., a run), shown with a spinner and the elapsed time
+pub(super) struct Status {
+    pub label: String,
+    pub started_at: Instant,
+}
+
+pub(super) struct PanelInfo {
+    pub buddy: String,
+    pub model: String,
+    pub conversation: String,
+    pub bundles: Vec<String>,
+}
+
+impl App {
+    pub fn new(buddy: &Buddy, conversation: &Conversation, plain: bool) -> Self {
+        let mut app = Self {
+            msgs: Vec::new(),
+            input: new_input(),
+            scroll: 0,
+            status: None,
+            run_progress: buddy.run_progress(),
+            panel: PanelInfo {
+                buddy: String::new(),
+                model: String::new(),
+                conversation: String::new(),
+                bundles: Vec::new(),
+            },
+            sync_status: synced_status(None),
+            logs: Vec::new(),
+            plain,
+            should_quit: false,
+            rendered: None,
+        };
+        app.set_buddy(buddy, conversation);
+
+        app
+    }
+
+    // * Updates the side panel after the buddy or the conversation changed
+    pub fn set_buddy(&mut self, buddy: &Buddy, conversation: &Conversation) {
+        self.run_progress = buddy.run_progress();
+        self.panel = PanelInfo {
+            buddy: buddy.name().to_string(),
+            model: buddy.model().to_string(),
+            conversation: conversation.display_name(),
+            bundles: buddy.bundle_names().into_iter().map(String::from).collect(),
+        };
+    }
+
+    pub fn set_msgs(&mut self, msgs: Vec<ThreadMsg>) {
+        self.msgs = msgs;
+        self.scroll = 0;
+        self.rendered = None;
+    }
+
+    // * Adds a message sent or received in this session (without reloading the thread)
+    pub fn push_msg(&mut self, role: &str, text: &str) {
+        self.msgs.push(ThreadMsg {
+            id: String::new(),
+            role: role.to_string(),
+            created_at: Utc::now().timestamp(),
+            text: text.to_string(),
+            citations: Vec::new(),
+        });
+        self.scroll = 0;
+    }
+
+    pub fn start_status(&mut self, label: impl Into<String>) {
+        self.status = Some(Status {
+            label: label.into(),
+            started_at: Instant::now(),
+        });
+    }
+
+    pub fn stop_status(&mut self) {
+        self.status = None;
+    }
+
+    // * Returns the status line, with the spinner frame of the elapsed time
+    pub fn status_line(&self) -> Option<String> {
+        self.status.as_ref().map(|status| {
+            let elapsed = status.started_at.elapsed();
+            let label = match self.run_progress.borrow().as_ref() {
+                Some(progress) => progress.to_string(),
+                None => status.label.clone(),
+            };
+            format!(
+                "{} {label} ({:.1}s)",
+                spinner_frame(elapsed),
+                elapsed.as_secs_f32()
+            )
+        })
+    }
+
+    // * Moves the captured console logs (e.g., uploads, errors) to the side panel
+    pub fn update_logs(&mut self) {
+        self.logs.extend(take_captured_logs());
+        if self.logs.len() > MAX_LOG_LINES {
+            self.logs.drain(..self.logs.len() - MAX_LOG_LINES);
+        }
+    }
+
+    // * Handles a terminal event, and returns the command to run (if any)
+    // - While a task is in progress, the input can be edited and the conversation scrolled,
+    //   but no command is returned
+    pub fn handle_event(&mut self, event: Event) -> Option<Cmd> {
+        let Event::Key(key) = event else {
+            return None;
+        };
+        if key.kind != KeyEventKind::Press {
+            return None;
+        }
+
+        let cmd = self.handle_key(key);
+        if self.status.is_some() {
+            if matches!(cmd, Some(Cmd::Quit)) {
+                self.should_quit = true;
+            }
+            return None;
+        }
+
+        cmd
+    }
+
+    // * Returns the rendered conversation for the width (cached until the width or the messages change)
+    pub fn conversation_text(&mut self, width: u16) -> &Text<'static> {
+        let is_fresh = matches!(
+            &self.rendered,
+            Some((rendered_width, num_msgs, _)) if *rendered_width == width && *num_msgs == self.msgs.len()
+        );
+        if !is_fresh {
+            self.rendered = None;
+        }
+
+        let (_, _, text) = self.rendered.get_or_insert_with(|| {
+            let text = render_msgs(&self.msgs, width as usize, self.plain);
+            (width, self.msgs.len(), text)
+        });
+
+        text
+    }
+}
+
+// * Private Functions
+impl App {
+    fn handle_key(&mut self, key: KeyEvent) -> Option<Cmd> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+        let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+
+        match key.code {
+            KeyCode::Char('c') | KeyCode::Char('d') if ctrl => Some(Cmd::Quit),
+            // Refresh Actions (same as `/rc`, `/ri`, `/rf`, `/ra`)
+            KeyCode::F(2) => Some(Cmd::RefreshConversation),
+            KeyCode::F(3) => Some(Cmd::RefreshInstructions),
+            KeyCode::F(4) => Some(Cmd::RefreshFiles),
+            KeyCode::F(5) => Some(Cmd::RefreshAll),
+            // Scroll
+            KeyCode::PageUp => {
+                self.scroll = self.scroll.saturating_add(SCROLL_PAGE_LINES);
+                None
+            }
+            KeyCode::PageDown => {
+                self.scroll = self.scroll.saturating_sub(SCROLL_PAGE_LINES);
+                None
+            }
+            KeyCode::Up if ctrl => {
+                self.scroll = self.scroll.saturating_add(1);
+                None
+            }
+            KeyCode::Down if ctrl => {
+                self.scroll = self.scroll.saturating_sub(1);
+                None
+            }
+            // Input (`Alt+Enter` or `Shift+Enter` for a new line)
+            KeyCode::Enter if alt || shift => {
+                self.input.insert_newline();
+                None
+            }
+            KeyCode::Enter => {
+                if self.status.is_some() {
+                    return None;
+                }
+                self.submit_input()
+            }
+            _ => {
+                self.input.input(key);
+                None
+            }
+        }
+    }
+
+    fn submit_input(&mut self) -> Option<Cmd> {
+        let input = self.input.lines().join("\n");
+        let input = input.trim();
+        if input.is_empty() {
+            return None;
+        }
+
+        let cmd = Cmd::from_input(input);
+        self.input = new_input();
+
+        cmd.map_err(|err| error!("{err}")).ok()
+    }
+}
+
+fn new_input() -> TextArea<'static> {
+    let mut input = TextArea::default();
+    input.set_placeholder_text(INPUT_PLACEHOLDER);
+    input.set_cursor_line_style(Default::default());
+
+    input
+}
+
+// * Renders the messages as the REPL prints them (Markdown answers unless `plain`)
+fn render_msgs(msgs: &[ThreadMsg], width: usize, plain: bool) -> Text<'static> {
+    let width = width.max(1);
+    let mut text = String::new();
+
+    for msg in msgs {
+        text.push_str(&format!(
+            "{} {}\n",
+            style(msg.role_title()).bold(),
+            style(format_timestamp(msg.created_at)).dim()
+        ));
+
+        if msg.role == "assistant" && !plain {
+            text.push_str(&format!("{}\n", icon_res()));
+            text.push_str(&render_markdown(&msg.text, width));
+        } else {
+            let lines: Vec<String> = wrap(&msg.text, width)
+                .into_iter()
+                .map(|line| line.into_owned())
+                .collect();
+            text.push_str(&lines.join("\n"));
+        }
+        text.push_str("\n\n");
+    }
+
+    text.into_text().unwrap_or_else(|_| {
+        Text::from(
+            text.lines()
+                .map(|line| Line::from(line.to_string()))
+                .collect::<Vec<_>>(),
+        )
+    })
+}
@@ -0,0 +1,255 @@
+// Full-screen chat mode (`--tui`), with the same commands and refresh actions as the REPL
+mod app;
+mod ui;
+
+use std::{future::Future, time::Duration};
+
+use chrono::Utc;
+use crossterm::event::EventStream;
+use futures::StreamExt;
+use ratatui::DefaultTerminal;
+use tokio::time::interval;
+use tracing::{info, warn};
+
+use crate::{
+    buddy::{Buddy, Conversation},
+    cmd::Cmd,
+    export::format_timestamp,
+    logs::{start_log_capture, stop_log_capture},
+    ok_or_print,
+    utils::cli::icon_check,
+    Result,
+};
+
+use self::app::App;
+
+// TODO: Define the CONSTANTS in a separate file constants.rs
+const TICK_MS: u64 = 100;
+
+// * Runs the TUI until quit, the console logs are shown in the side panel meanwhile
+// - `buddy` and `conversation` are updated by the commands (e.g., `/ra`, `/switch`)
+pub async fn run_tui(
+    buddy: &mut Buddy,
+    conversation: &mut Conversation,
+    plain: bool,
+) -> Result<()> {
+    let mut app = App::new(buddy, conversation, plain);
+    app.set_msgs(buddy.conversation_msgs(conversation).await?);
+
+    start_log_capture();
+    let mut terminal = ratatui::init();
+    let res = event_loop(&mut terminal, &mut app, buddy, conversation).await;
+    ratatui::restore();
+    stop_log_capture();
+
+    res
+}
+
+async fn event_loop(
+    terminal: &mut DefaultTerminal,
+    app: &mut App,
+    buddy: &mut Buddy,
+    conversation: &mut Conversation,
+) -> Result<()> {
+    let mut events = EventStream::new();
+    let mut ticker = interval(Duration::from_millis(TICK_MS));
+
+    while !app.should_quit {
+        app.update_logs();
+        terminal.draw(|frame| ui::draw(frame, app))?;
+
+        let cmd = tokio::select! {
+            Some(event) = events.next() => app.handle_event(event?),
+            _ = ticker.tick() => None,
+        };
+        if let Some(cmd) = cmd {
+            run_cmd(terminal, app, &mut events, buddy, conversation, cmd).await?;
+        }
+    }
+
+    Ok(())
+}
+
+// * Runs the command, the errors are shown in the logs (like the REPL, they do not quit the TUI)
+async fn run_cmd(
+    terminal: &mut DefaultTerminal,
+    app: &mut App,
+    events: &mut EventStream,
+    buddy: &mut Buddy,
+    conversation: &mut Conversation,
+    cmd: Cmd,
+) -> Result<()> {
+    // Set when the conversation (or its thread) was replaced
+    let mut new_conversation: Option<Conversation> = None;
+
+    match cmd {
+        Cmd::Quit => app.should_quit = true,
+        Cmd::Chat(msg) => {
+            app.push_msg("user", &msg);
+            let task = buddy.chat(conversation, &msg);
+            match ok_or_print(with_status(terminal, app, events, "Running", task).await?) {
+                Some(res) => app.push_msg("assistant", &res),
+                // The message might not be in the thread (e.g., budget exceeded)
+                None => reload_msgs(terminal, app, events, buddy, conversation).await?,
+            }
+        }
+        Cmd::RefreshAll => {
+            let dir = buddy.dir().to_path_buf();
+            let task = async {
+                let buddy = Buddy::init_from_dir(&dir, true).await?;
+                let conversation = buddy.load_or_create_conversation(true).await?;
+                Ok((buddy, conversation))
+            };
+            let label = "Recreating the assistant";
+            if let Some((new_buddy, conversation)) =
+                ok_or_print(with_status(terminal, app, events, label, task).await?)
+            {
+                *buddy = new_buddy;
+                new_conversation = Some(conversation);
+                app.sync_status = synced_status(None);
+            }
+        }
+        Cmd::RefreshConversation => {
+            let task = buddy.load_or_create_conversation(true);
+            let label = "Creating a new thread";
+            new_conversation = ok_or_print(with_status(terminal, app, events, label, task).await?);
+        }
+        Cmd::RefreshInstructions => {
+            let task = async {
+                buddy.upload_instructions().await?;
+                buddy.load_or_create_conversation(true).await
+            };
+            let label = "Uploading the instructions";
+            new_conversation = ok_or_print(with_status(terminal, app, events, label, task).await?);
+        }
+        Cmd::RefreshFiles => {
+            let task = async {
+                let num_uploaded = buddy.upload_files(true).await?;
+                let conversation = buddy.load_or_create_conversation(true).await?;
+                Ok((num_uploaded, conversation))
+            };
+            let label = "Uploading the files";
+            if let Some((num_uploaded, conversation)) =
+                ok_or_print(with_status(terminal, app, events, label, task).await?)
+            {
+                new_conversation = Some(conversation);
+                app.sync_status = synced_status(Some(num_uploaded));
+            }
+        }
+        Cmd::NewConversation(name) => {
+            let task = buddy.new_conversation(name.as_deref());
+            let label = "Creating the conversation";
+            new_conversation = ok_or_print(with_status(terminal, app, events, label, task).await?);
+        }
+        Cmd::SwitchConversation(name) => {
+            let task = buddy.switch_conversation(&name, false);
+            let label = "Loading the conversation";
+            new_conversation = ok_or_print(with_status(terminal, app, events, label, task).await?);
+        }
+        Cmd::Fork(num_msgs) => {
+            let task = buddy.fork_conversation(conversation, num_msgs);
+            let label = "Forking the conversation";
+            new_conversation = ok_or_print(with_status(terminal, app, events, label, task).await?);
+        }
+        Cmd::Retry => {
+            let task = buddy.retry_last_exchange(conversation);
+            ok_or_print(with_status(terminal, app, events, "Running", task).await?);
+            reload_msgs(terminal, app, events, buddy, conversation).await?;
+        }
+        Cmd::Undo => {
+            let task = buddy.undo_last_exchange(conversation);
+            if let Some(msg) =
+                ok_or_print(with_status(terminal, app, events, "Undoing", task).await?)
+            {
+                info!("{} Last Exchange Removed - '{}'", icon_check(), msg);
+            }
+            reload_msgs(terminal, app, events, buddy, conversation).await?;
+        }
+        Cmd::RenameConversation { name, new_name } => {
+            let name = name.unwrap_or_else(|| conversation.name.clone());
+            if let Some(renamed) = ok_or_print(buddy.rename_conversation(&name, &new_name)) {
+                info!(
+                    "{} Conversation '{name}' Renamed to '{new_name}'",
+                    icon_check()
+                );
+                if conversation.name == name {
+                    *conversation = renamed;
+                }
+            }
+        }
+        // ! NOTE: These commands print to the terminal, so they are only available in the REPL
+        Cmd::CodeList
+        | Cmd::CodeSave { .. }
+        | Cmd::CodeCopy { .. }
+        | Cmd::Export { .. }
+        | Cmd::History(_)
+        | Cmd::ListConversations
+        | Cmd::Steps
+        | Cmd::Usage => {
+            warn!("This command is not available in the TUI, run without `--tui` to use it")
+        }
+    }
+
+    if let Some(new_conversation) = new_conversation {
+        *conversation = new_conversation;
+        reload_msgs(terminal, app, events, buddy, conversation).await?;
+    }
+    // The title might have changed (e.g., after the first exchange)
+    *conversation = buddy.reload_conversation(conversation)?;
+    app.set_buddy(buddy, conversation);
+
+    Ok(())
+}
+
+// * Awaits the task while animating the status line, and handling the input and scroll keys
+// - The outer `Result` is for the terminal errors, the inner one is the task result
+async fn with_status<T>(
+    terminal: &mut DefaultTerminal,
+    app: &mut App,
+    events: &mut EventStream,
+    label: &str,
+    task: impl Future<Output = T>,
+) -> Result<T> {
+    app.start_status(label);
+    tokio::pin!(task);
+    let mut ticker = interval(Duration::from_millis(TICK_MS));
+
+    let res = loop {
+        tokio::select! {
+            res = &mut task => break res,
+            Some(event) = events.next() => {
+                app.handle_event(event?);
+            }
+            _ = ticker.tick() => (),
+        }
+        app.update_logs();
+        terminal.draw(|frame| ui::draw(frame, app))?;
+    };
+    app.stop_status();
+
+    Ok(res)
+}
+
+async fn reload_msgs(
+    terminal: &mut DefaultTerminal,
+    app: &mut App,
+    events: &mut EventStream,
+    buddy: &Buddy,
+    conversation: &Conversation,
+) -> Result<()> {
+    let task = buddy.conversation_msgs(conversation);
+    let label = "Loading the messages";
+    if let Some(msgs) = ok_or_print(with_status(terminal, app, events, label, task).await?) {
+        app.set_msgs(msgs);
+    }
+
+    Ok(())
+}
+
+fn synced_status(num_uploaded: Option<u32>) -> String {
+    let synced = format!("Synced at {}", format_timestamp(Utc::now().timestamp()));
+    match num_uploaded {
+        Some(num_uploaded) => format!("{synced} ({num_uploaded} uploaded)"),
+        None => synced,
+    }
+}
@@ -0,0 +1,156 @@
+use ansi_to_tui::IntoText;
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span, Text},
+    widgets::{Block, Borders, Paragraph},
+    Frame,
+};
+use textwrap::wrap;
+
+use super::app::App;
+
+// TODO: Define the CONSTANTS in a separate file constants.rs
+const PANEL_WIDTH: u16 = 32;
+const INPUT_HEIGHT: u16 = 6;
+const ACCENT_COLOR: Color = Color::Indexed(45);
+const KEY_HINTS: &[(&str, &str)] = &[
+    ("Enter", "Send"),
+    ("Alt+Enter", "New line"),
+    ("PgUp/PgDn", "Scroll"),
+    ("F2", "Refresh conversation"),
+    ("F3", "Refresh instructions"),
+    ("F4", "Refresh files"),
+    ("F5", "Refresh all"),
+    ("Ctrl+C", "Quit"),
+];
+
+// Layout
+// * Conversation and input on the left, side panel on the right
+pub(super) fn draw(frame: &mut Frame, app: &mut App) {
+    let [main_area, panel_area] =
+        Layout::horizontal([Constraint::Min(0), Constraint::Length(PANEL_WIDTH)])
+            .areas(frame.area());
+    let [conversation_area, status_area, input_area] = Layout::vertical([
+        Constraint::Min(0),
+        Constraint::Length(1),
+        Constraint::Length(INPUT_HEIGHT),
+    ])
+    .areas(main_area);
+
+    draw_conversation(frame, app, conversation_area);
+    draw_status(frame, app, status_area);
+    draw_input(frame, app, input_area);
+    draw_panel(frame, app, panel_area);
+}
+
+// Widgets
+fn draw_conversation(frame: &mut Frame, app: &mut App, area: Rect) {
+    let block = titled_block(&format!("Conversation - {}", app.panel.conversation));
+    let inner = block.inner(area);
+
+    // Keep the scroll within the conversation, the bottom is at `scroll == 0`
+    let num_lines = app.conversation_text(inner.width).lines.len() as u16;
+    let max_scroll = num_lines.saturating_sub(inner.height);
+    app.scroll = app.scroll.min(max_scroll);
+    let offset = max_scroll - app.scroll;
+
+    let text = app.conversation_text(inner.width).clone();
+    frame.render_widget(Paragraph::new(text).block(block).scroll((offset, 0)), area);
+}
+
+fn draw_status(frame: &mut Frame, app: &App, area: Rect) {
+    let line = match app.status_line() {
+        Some(status) => Line::styled(status, Style::new().fg(ACCENT_COLOR)),
+        None => Line::styled(
+            format!(" {} messages", app.msgs.len()),
+            Style::new().add_modifier(Modifier::DIM),
+        ),
+    };
+
+    frame.render_widget(Paragraph::new(line), area);
+}
+
+fn draw_input(frame: &mut Frame, app: &mut App, area: Rect) {
+    let title = if app.status.is_some() {
+        "Message (waiting for the task to finish)"
+    } else {
+        "Message"
+    };
+    app.input.set_block(titled_block(title));
+
+    frame.render_widget(&app.input, area);
+}
+
+fn draw_panel(frame: &mut Frame, app: &App, area: Rect) {
+    let mut lines = Vec::new();
+    push_section(&mut lines, "Buddy");
+    lines.push(Line::from(app.panel.buddy.clone()));
+    push_section(&mut lines, "Model");
+    lines.push(Line::from(app.panel.model.clone()));
+    push_section(&mut lines, "Conversation");
+    lines.push(Line::from(app.panel.conversation.clone()));
+    push_section(&mut lines, "Bundles");
+    lines.extend(
+        app.panel
+            .bundles
+            .iter()
+            .map(|bundle| Line::from(format!("- {bundle}"))),
+    );
+    push_section(&mut lines, "Sync");
+    lines.push(Line::from(app.sync_status.clone()));
+
+    push_section(&mut lines, "Keys");
+    lines.extend(KEY_HINTS.iter().map(|(key, action)| {
+        Line::from(vec![
+            Span::styled(format!("{key:<10}"), Style::new().fg(ACCENT_COLOR)),
+            Span::raw(*action),
+        ])
+    }));
+
+    // The logs take the rest of the panel, the latest at the bottom
+    push_section(&mut lines, "Logs");
+
+    let block = titled_block("Buddy");
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let info_height = (lines.len() as u16).min(inner.height);
+    let [info_area, logs_area] =
+        Layout::vertical([Constraint::Length(info_height), Constraint::Min(0)]).areas(inner);
+    frame.render_widget(Paragraph::new(lines), info_area);
+
+    // ! NOTE: The logs have ANSI styles (e.g., the icons), which `wrap` does not count in the width
+    let width = (logs_area.width as usize).max(1);
+    let wrapped_logs: Vec<String> = app
+        .logs
+        .iter()
+        .flat_map(|log| wrap(log, width))
+        .map(|line| line.into_owned())
+        .collect();
+    let num_visible = (logs_area.height as usize).min(wrapped_logs.len());
+    let logs = wrapped_logs[wrapped_logs.len() - num_visible..].join("\n");
+    let logs = logs
+        .into_text()
+        .unwrap_or_else(|_| Text::from(logs.clone()));
+    frame.render_widget(Paragraph::new(logs), logs_area);
+}
+
+// Helpers
+fn titled_block(title: &str) -> Block<'static> {
+    Block::new()
+        .borders(Borders::ALL)
+        .border_style(Style::new().fg(ACCENT_COLOR))
+        .title(title.to_string())
+}
+
+// * Pushes the section title, after a blank line (except for the first section)
+fn push_section(lines: &mut Vec<Line<'static>>, title: &str) {
+    if !lines.is_empty() {
+        lines.push(Line::default());
+    }
+    lines.push(Line::styled(
+        title.to_string(),
+        Style::new().add_modifier(Modifier::BOLD),
+    ));
+}
@@ -0,0 +1,296 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::OpenOptions,
+    io::{BufRead, BufReader, Write},
+    path::Path,
+};
+
+use chrono::{DateTime, Local, Utc};
+use console::style;
+use serde::{Deserialize, Serialize};
+
+use crate::Result;
+
+// Types
+// * One line of `.buddy/usage.jsonl`, for each completed run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageRecord {
+    // Unix timestamp (in seconds)
+    pub timestamp: i64,
+    pub buddy: String,
+    pub conversation: String,
+    pub thread_id: String,
+    pub model: String,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    // `None` when the model has no price in the config
+    pub cost: Option<f64>,
+}
+
+// * Price of a model, in dollars per 1K tokens
+#[derive(Debug, Clone, Deserialize)]
+pub struct ModelPrice {
+    pub prompt: f64,
+    pub completion: f64,
+}
+
+// * Spending caps, from the `[budget]` of `buddy.toml` (all optional)
+#[derive(Debug, Clone, Deserialize)]
+pub struct Budget {
+    pub daily_tokens: Option<u64>,
+    pub daily_cost: Option<f64>,
+    pub monthly_tokens: Option<u64>,
+    pub monthly_cost: Option<f64>,
+    pub message_tokens: Option<u64>,
+    pub message_cost: Option<f64>,
+    // Fraction of a cap (e.g., `0.8`) from which a warning is printed
+    #[serde(default = "default_warn_threshold")]
+    pub warn_threshold: f64,
+}
+
+// * Estimated usage of the next run, for the per message caps
+pub struct UsageEstimate {
+    pub tokens: u64,
+    pub cost: Option<f64>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct UsageTotals {
+    pub runs: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cost: f64,
+    // Runs without a known price, not counted in `cost`
+    pub unpriced_runs: u64,
+}
+
+impl UsageRecord {
+    pub fn new(
+        buddy: &str,
+        conversation: &str,
+        thread_id: &str,
+        model: &str,
+        prompt_tokens: u64,
+        completion_tokens: u64,
+        prices: &HashMap<String, ModelPrice>,
+    ) -> Self {
+        let cost = model_price(prices, model).map(|price| {
+            (prompt_tokens as f64 * price.prompt + completion_tokens as f64 * price.completion)
+                / 1000.
+        });
+
+        Self {
+            timestamp: Utc::now().timestamp(),
+            buddy: buddy.to_string(),
+            conversation: conversation.to_string(),
+            thread_id: thread_id.to_string(),
+            model: model.to_string(),
+            prompt_tokens,
+            completion_tokens,
+            cost,
+        }
+    }
+
+    // * Local day of the run (e.g., `2026-10-18`)
+    pub fn day(&self) -> String {
+        DateTime::from_timestamp(self.timestamp, 0)
+            .map(|date| date.with_timezone(&Local).format("%Y-%m-%d").to_string())
+            .unwrap_or_default()
+    }
+}
+
+impl UsageTotals {
+    pub fn add(&mut self, record: &UsageRecord) {
+        self.runs += 1;
+        self.prompt_tokens += record.prompt_tokens;
+        self.completion_tokens += record.completion_tokens;
+        match record.cost {
+            Some(cost) => self.cost += cost,
+            None => self.unpriced_runs += 1,
+        }
+    }
+
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+fn default_warn_threshold() -> f64 {
+    0.8
+}
+
+// * Returns the price of the model, or of the longest price name prefixing the model
+//   (e.g., `gpt-3.5-turbo` for `gpt-3.5-turbo-1106`)
+pub fn model_price<'a>(
+    prices: &'a HashMap<String, ModelPrice>,
+    model: &str,
+) -> Option<&'a ModelPrice> {
+    prices.get(model).or_else(|| {
+        prices
+            .iter()
+            .filter(|(name, _)| model.starts_with(name.as_str()))
+            .max_by_key(|(name, _)| name.len())
+            .map(|(_, price)| price)
+    })
+}
+
+// Budget
+// * Returns an error when a cap is already reached (or would be by the next run, for the per message caps),
+//   otherwise the warnings for the caps above the warning threshold
+pub fn check_budget(
+    budget: &Budget,
+    records: &[UsageRecord],
+    estimate: &UsageEstimate,
+) -> Result<Vec<String>> {
+    let today = Local::now().format("%Y-%m-%d").to_string();
+    let month = Local::now().format("%Y-%m").to_string();
+
+    let mut daily = UsageTotals::default();
+    let mut monthly = UsageTotals::default();
+    for record in records {
+        let day = record.day();
+        if day == today {
+            daily.add(record);
+        }
+        if day.starts_with(&month) {
+            monthly.add(record);
+        }
+    }
+
+    // (label, used, cap, is_cost)
+    let checks = [
+        (
+            "Daily token",
+            daily.total_tokens() as f64,
+            budget.daily_tokens.map(|cap| cap as f64),
+            false,
+        ),
+        ("Daily cost", daily.cost, budget.daily_cost, true),
+        (
+            "Monthly token",
+            monthly.total_tokens() as f64,
+            budget.monthly_tokens.map(|cap| cap as f64),
+            false,
+        ),
+        ("Monthly cost", monthly.cost, budget.monthly_cost, true),
+        (
+            "Per message token",
+            estimate.tokens as f64,
+            budget.message_tokens.map(|cap| cap as f64),
+            false,
+        ),
+        (
+            "Per message cost",
+            estimate.cost.unwrap_or(0.),
+            budget.message_cost,
+            true,
+        ),
+    ];
+
+    let mut warnings = Vec::new();
+    for (label, used, cap, is_cost) in checks {
+        let Some(cap) = cap else {
+            continue;
+        };
+        let amounts = if is_cost {
+            format!("${used:.4} of ${cap:.4}")
+        } else {
+            format!("{used:.0} of {cap:.0} tokens")
+        };
+
+        if used >= cap {
+            return Err(format!(
+                "{label} budget exceeded ({amounts}), the run was not created. \
+                 Change the [budget] of buddy.toml to continue."
+            )
+            .into());
+        }
+        if used >= cap * budget.warn_threshold {
+            warnings.push(format!(
+                "{label} budget at {:.0}% ({amounts})",
+                used / cap * 100.
+            ));
+        }
+    }
+
+    Ok(warnings)
+}
+
+// Usage File
+pub fn append_usage(file: &Path, record: &UsageRecord) -> Result<()> {
+    let mut writer = OpenOptions::new().create(true).append(true).open(file)?;
+    writeln!(writer, "{}", serde_json::to_string(record)?)?;
+
+    Ok(())
+}
+
+// * Returns all the records, or none if the file does not exist yet
+pub fn load_usage(file: &Path) -> Result<Vec<UsageRecord>> {
+    if !file.exists() {
+        return Ok(Vec::new());
+    }
+
+    let reader = BufReader::new(std::fs::File::open(file)?);
+    let mut records = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            records.push(serde_json::from_str(&line)?);
+        }
+    }
+
+    Ok(records)
+}
+
+// Reports
+pub fn print_usage_summary(records: &[UsageRecord]) {
+    if records.is_empty() {
+        println!("{}", style("No usage recorded yet").dim());
+        return;
+    }
+
+    println!("{}", style("By Day").bold());
+    print_totals(&group_totals(records, UsageRecord::day));
+
+    println!("\n{}", style("By Buddy").bold());
+    print_totals(&group_totals(records, |record| record.buddy.clone()));
+}
+
+pub fn format_totals(totals: &UsageTotals) -> String {
+    let mut text = format!(
+        "{} runs - {} tokens ({} prompt, {} completion) - ${:.4}",
+        totals.runs,
+        totals.total_tokens(),
+        totals.prompt_tokens,
+        totals.completion_tokens,
+        totals.cost
+    );
+    if totals.unpriced_runs > 0 {
+        text.push_str(&format!(" ({} runs without price)", totals.unpriced_runs));
+    }
+
+    text
+}
+
+fn group_totals(
+    records: &[UsageRecord],
+    key_fn: impl Fn(&UsageRecord) -> String,
+) -> BTreeMap<String, UsageTotals> {
+    let mut totals_by_key: BTreeMap<String, UsageTotals> = BTreeMap::new();
+    for record in records {
+        totals_by_key.entry(key_fn(record)).or_default().add(record);
+    }
+
+    totals_by_key
+}
+
+fn print_totals(totals_by_key: &BTreeMap<String, UsageTotals>) {
+    let key_width = totals_by_key.keys().map(String::len).max().unwrap_or(0);
+    for (key, totals) in totals_by_key {
+        println!(
+            "  {} {}",
+            style(format!("{key:<key_width$}")).color256(45),
+            format_totals(totals)
+        );
+    }
+}
@@ -0,0 +1,478 @@
+use std::{
+    ffi::OsStr,
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use derive_more::Display;
+use encoding_rs::Encoding;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::{utils::files::XFile, Result};
+
+// TODO: Define the CONSTANTS in a separate file constants.rs
+// Number of the first bytes checked for a NUL byte
+const BINARY_CHECK_LEN: usize = 8000;
+
+// Types
+// * How the files are written in a bundle
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BundleFormat {
+    // `// ==== File Path: <path>` header before each file
+    #[default]
+    Commented,
+    // `## <path>` header, and the content in a code block fenced with its language
+    Markdown,
+    // `<file path="<path>">` tags around each file
+    Xml,
+    // One `{"path": ..., "content": ...}` JSON object per line
+    Jsonl,
+}
+
+// * Files of a bundle file, and their sections as written in the bundle
+#[derive(Default)]
+struct BundlePart {
+    files: Vec<BundledFile>,
+    content: String,
+}
+
+// * Entry of the table of contents
+#[derive(Serialize)]
+struct BundledFile {
+    path: String,
+    lines: usize,
+    // In bytes (of the decoded text)
+    size: usize,
+    language: String,
+}
+
+// * Line of a `jsonl` bundle
+#[derive(Serialize)]
+struct JsonlFile<'a> {
+    path: &'a str,
+    content: &'a str,
+}
+
+// * Settings of a bundle, for the files and their parts
+pub struct BundleOptions<'a> {
+    // Shown in the table of contents, at the start of each bundle file
+    pub name: &'a str,
+    pub description: Option<&'a str>,
+    // The paths in the bundle are relative to it (e.g., `src/main.rs`)
+    pub root_dir: &'a Path,
+    pub format: BundleFormat,
+    // Above this size (in bytes), the bundle is split into numbered parts
+    pub max_size: Option<u64>,
+    // Above this size (in bytes), a file is skipped
+    pub max_file_size: Option<u64>,
+    // Encoding of the text files, the invalid sequences are replaced (e.g., `�`)
+    pub encoding: &'static Encoding,
+}
+
+// * What is bundled: a file, or a text generated for the bundle (e.g., a git diff)
+pub enum BundleItem {
+    File(PathBuf),
+    Generated {
+        // Shown instead of the file path (e.g., `git diff --staged`)
+        path: String,
+        language: String,
+        content: String,
+    },
+}
+
+// * Files written by the bundler
+pub struct BundleOutput {
+    pub part_files: Vec<PathBuf>,
+    pub skipped: Vec<SkippedFile>,
+}
+
+// * File not in the bundle, reported at the end of the sync
+#[derive(Debug)]
+pub struct SkippedFile {
+    pub path: String,
+    pub reason: SkipReason,
+}
+
+#[derive(Debug, Display)]
+pub enum SkipReason {
+    #[display("binary file")]
+    Binary,
+    #[display(
+        "{:.1} KB, larger than the {:.1} KB max file size",
+        *_0 as f64 / 1024.0,
+        *_1 as f64 / 1024.0
+    )]
+    TooLarge(u64, u64),
+    #[display("cannot read: {_0}")]
+    Unreadable(String),
+    #[display("another file has the same name")]
+    DuplicateName,
+}
+
+// * First line of a `jsonl` bundle, with the table of contents
+#[derive(Serialize)]
+struct JsonlHeader<'a> {
+    bundle: &'a str,
+    description: Option<&'a str>,
+    // `[part, number of parts]`, when the bundle is split
+    part: Option<(usize, usize)>,
+    files: &'a [BundledFile],
+}
+
+// File Bundler
+// * Bundles the files into `dst_file`, or into numbered parts when larger than `max_size`
+// - Each part starts with a table of contents of its files (not counted in `max_size`)
+// - The binary, unreadable and too large files are skipped (and returned with the reason)
+// - The bundle is split at the file boundaries, so a file larger than `max_size` gets its own part
+// - The parts are named `<dst_file stem>-part-<n>.<ext>`, and returned in order
+pub fn bundle_to_files(
+    items: Vec<BundleItem>,
+    dst_file: &Path,
+    options: &BundleOptions,
+) -> Result<BundleOutput> {
+    let mut parts: Vec<BundlePart> = vec![BundlePart::default()];
+    let mut skipped = Vec::new();
+
+    for item in items {
+        let (path, language, content) = match item {
+            BundleItem::File(file) => {
+                if !file.is_file() {
+                    return Err(format!("Cannot Bundle '{:?}' is not a file", file).into());
+                }
+                let path = relative_path(&file, options.root_dir);
+                match read_text(&file, options) {
+                    Ok(content) => (path, file_language(&file), content),
+                    Err(reason) => {
+                        skipped.push(SkippedFile { path, reason });
+                        continue;
+                    }
+                }
+            }
+            BundleItem::Generated {
+                path,
+                language,
+                content,
+            } => (path, language, content),
+        };
+        let section = bundle_section(&path, &language, &content, options.format)?;
+
+        if let Some(max_size) = options.max_size {
+            if section.len() as u64 > max_size {
+                warn!(
+                    "File '{}' is larger than the bundle max size, it is bundled in its own part",
+                    path
+                );
+            }
+            let part_len = parts.last().map(|part| part.content.len()).unwrap_or(0);
+            if part_len > 0 && (part_len + section.len()) as u64 > max_size {
+                parts.push(BundlePart::default());
+            }
+        }
+        if let Some(part) = parts.last_mut() {
+            part.files.push(BundledFile {
+                path,
+                lines: content.lines().count(),
+                size: content.len(),
+                language,
+            });
+            part.content.push_str(&section);
+        }
+    }
+
+    // A single part keeps the name of the bundle
+    let num_parts = parts.len();
+    if num_parts == 1 {
+        fs::write(dst_file, parts[0].to_text(options, None)?)?;
+        return Ok(BundleOutput {
+            part_files: vec![dst_file.to_path_buf()],
+            skipped,
+        });
+    }
+
+    let stem = dst_file.file_stem().and_then(OsStr::to_str).unwrap_or("");
+    let mut part_files = Vec::new();
+    for (idx, part) in parts.iter().enumerate() {
+        let part_file = dst_file.with_file_name(format!(
+            "{stem}-part-{}.{}",
+            idx + 1,
+            dst_file.x_extension()
+        ));
+        fs::write(
+            &part_file,
+            part.to_text(options, Some((idx + 1, num_parts)))?,
+        )?;
+        part_files.push(part_file);
+    }
+
+    Ok(BundleOutput {
+        part_files,
+        skipped,
+    })
+}
+
+// Impls
+impl BundlePart {
+    // * Returns the part, starting with the table of contents of its files
+    // - `part` is `(part, number of parts)`, when the bundle is split
+    fn to_text(&self, options: &BundleOptions, part: Option<(usize, usize)>) -> Result<String> {
+        let title = match part {
+            Some((part, num_parts)) => format!("{} (part {part} of {num_parts})", options.name),
+            None => options.name.to_string(),
+        };
+        let files = &self.files;
+
+        let header = match options.format {
+            BundleFormat::Commented => {
+                let mut header = format!("// ==== Bundle: {title}\n");
+                if let Some(description) = options.description {
+                    for line in description.lines() {
+                        header.push_str(&format!("// {line}\n"));
+                    }
+                }
+                header.push_str(&format!("//\n// Files ({}):\n", files.len()));
+                for file in files {
+                    header.push_str(&format!("// - {}\n", file_summary(file)));
+                }
+                header
+            }
+            BundleFormat::Markdown => {
+                let mut header = format!("# Bundle: {title}\n\n");
+                if let Some(description) = options.description {
+                    header.push_str(&format!("{}\n\n", description.trim_end()));
+                }
+                header.push_str(&format!("Files ({}):\n\n", files.len()));
+                header.push_str("| Path | Lines | Size | Language |\n");
+                header.push_str("| --- | --- | --- | --- |\n");
+                for file in files {
+                    header.push_str(&format!(
+                        "| {} | {} | {} | {} |\n",
+                        escape_md_cell(&file.path),
+                        file.lines,
+                        format_size(file.size),
+                        escape_md_cell(&file.language)
+                    ));
+                }
+                header
+            }
+            BundleFormat::Xml => {
+                let mut header = format!("<bundle name=\"{}\">\n", escape_xml(&title));
+                if let Some(description) = options.description {
+                    header.push_str(&format!(
+                        "<description>{}</description>\n",
+                        escape_xml(description.trim_end())
+                    ));
+                }
+                header.push_str("<files>\n");
+                for file in files {
+                    header.push_str(&format!(
+                        "<file path=\"{}\" lines=\"{}\" size=\"{}\" language=\"{}\"/>\n",
+                        escape_xml(&file.path),
+                        file.lines,
+                        file.size,
+                        escape_xml(&file.language)
+                    ));
+                }
+                header.push_str("</files>\n");
+                header
+            }
+            BundleFormat::Jsonl => {
+                let header = JsonlHeader {
+                    bundle: options.name,
+                    description: options.description,
+                    part,
+                    files,
+                };
+                format!("{}\n", serde_json::to_string(&header)?)
+            }
+        };
+
+        // The XML files are inside the `<bundle>` element, so the bundle has a single root
+        let footer = match options.format {
+            BundleFormat::Xml => "</bundle>\n",
+            _ => "",
+        };
+
+        Ok(format!("{header}\n{}{footer}", self.content))
+    }
+}
+
+// * Returns the table of contents line of the file (e.g., `src/main.rs (120 lines, 3.4 KB, rust)`)
+fn file_summary(file: &BundledFile) -> String {
+    format!(
+        "{} ({} lines, {}, {})",
+        file.path,
+        file.lines,
+        format_size(file.size),
+        file.language
+    )
+}
+
+fn format_size(size: usize) -> String {
+    if size < 1024 {
+        format!("{size} B")
+    } else {
+        format!("{:.1} KB", size as f64 / 1024.0)
+    }
+}
+
+// * Splits the `]]>` of the text, as it would end the CDATA section
+fn escape_cdata(text: &str) -> String {
+    text.replace("]]>", "]]]]><![CDATA[>")
+}
+
+// * Escapes the `|` of a Markdown table cell
+fn escape_md_cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+// * Returns the text of the file, decoded with the bundle encoding, or why it is skipped
+fn read_text(file: &Path, options: &BundleOptions) -> std::result::Result<String, SkipReason> {
+    let size = fs::metadata(file)
+        .map_err(|err| SkipReason::Unreadable(err.to_string()))?
+        .len();
+    if let Some(max_file_size) = options.max_file_size {
+        if size > max_file_size {
+            return Err(SkipReason::TooLarge(size, max_file_size));
+        }
+    }
+
+    let bytes = fs::read(file).map_err(|err| SkipReason::Unreadable(err.to_string()))?;
+    if is_binary(&bytes) {
+        return Err(SkipReason::Binary);
+    }
+
+    let (content, _, had_errors) = options.encoding.decode(&bytes);
+    if had_errors {
+        debug!(
+            "File '{}' is not valid {}, the invalid sequences were replaced",
+            file.to_string_lossy(),
+            options.encoding.name()
+        );
+    }
+
+    Ok(content.into_owned())
+}
+
+// * Like git, a file with a NUL byte in its first bytes is binary
+// - ! NOTE: The UTF-16 files have NUL bytes, but they start with a BOM (decoded by the encoding)
+fn is_binary(bytes: &[u8]) -> bool {
+    if bytes.starts_with(&[0xFF, 0xFE]) || bytes.starts_with(&[0xFE, 0xFF]) {
+        return false;
+    }
+    bytes.iter().take(BINARY_CHECK_LEN).any(|byte| *byte == 0)
+}
+
+// * Returns the file, with its path, as written in the bundle
+fn bundle_section(
+    path: &str,
+    language: &str,
+    content: &str,
+    format: BundleFormat,
+) -> Result<String> {
+    let section = match format {
+        BundleFormat::Commented => {
+            format!(
+                "\n// ==== File Path: {path}\n\n{}\n\n\n",
+                content.trim_end()
+            )
+        }
+        BundleFormat::Markdown => {
+            let fence = code_fence(content);
+            format!(
+                "## {path}\n\n{fence}{}\n{}\n{fence}\n\n",
+                language,
+                content.trim_end()
+            )
+        }
+        BundleFormat::Xml => format!(
+            "<file path=\"{}\">\n<![CDATA[\n{}\n]]>\n</file>\n\n",
+            escape_xml(path),
+            escape_cdata(content.trim_end())
+        ),
+        BundleFormat::Jsonl => {
+            let line = JsonlFile { path, content };
+            format!("{}\n", serde_json::to_string(&line)?)
+        }
+    };
+
+    Ok(section)
+}
+
+// Paths
+// * Returns the normalised path of the file, relative to `root_dir` (with `/` separators)
+// - Falls back to the normalised absolute path when the file is outside of `root_dir`
+pub fn relative_path(file: &Path, root_dir: &Path) -> String {
+    let file = normalize_path(file);
+    let root_dir = normalize_path(root_dir);
+    let path = file.strip_prefix(&root_dir).unwrap_or(&file);
+
+    path.to_string_lossy().replace('\\', "/")
+}
+
+// * Returns the absolute path without the `.` and `..` (e.g., `buddy/../src/main.rs` is `src/main.rs`)
+fn normalize_path(path: &Path) -> PathBuf {
+    if let Ok(path) = path.canonicalize() {
+        return path;
+    }
+
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+
+    normalized
+}
+
+// Markdown
+// * Returns a fence longer than any backtick run of the content (at least 3 backticks)
+fn code_fence(content: &str) -> String {
+    let mut max_run = 0;
+    let mut run = 0;
+    for char in content.chars() {
+        if char == '`' {
+            run += 1;
+            max_run = max_run.max(run);
+        } else {
+            run = 0;
+        }
+    }
+
+    "`".repeat((max_run + 1).max(3))
+}
+
+// * Returns the language of the file, from its extension (also used for the code blocks)
+fn file_language(file: &Path) -> String {
+    let language = match file.x_extension() {
+        "" => "text",
+        "rs" => "rust",
+        "md" => "markdown",
+        "py" => "python",
+        "js" | "mjs" | "cjs" => "javascript",
+        "ts" => "typescript",
+        "tsx" => "tsx",
+        "sh" => "bash",
+        "yml" | "yaml" => "yaml",
+        "h" => "c",
+        "hpp" | "cc" => "cpp",
+        "kt" => "kotlin",
+        "rb" => "ruby",
+        ext => ext,
+    };
+
+    language.to_string()
+}
@@ -1,23 +1,62 @@
-use console::{style, Style, StyledObject};
-use dialoguer::{theme::ColorfulTheme, Input};
+use std::{
+    fmt::Display,
+    future::Future,
+    time::{Duration, Instant},
+};
 
-use crate::Result;
+use console::{style, Style, StyledObject, Term};
+use dialoguer::{theme::ColorfulTheme, Confirm};
+use similar::{ChangeTag, TextDiff};
+use tokio::{sync::watch, time::interval};
+
+use crate::{logs::set_console_printer, utils::markdown::render_markdown, Result};
+
+// TODO: Define the CONSTANTS in a separate file constants.rs
+const DEFAULT_TERM_WIDTH: usize = 80;
+const SPINNER_FRAMES: &[&str] = &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+const SPINNER_FRAME_MS: u128 = 100;
+// Label of the status line until the task reports its first status
+const STATUS_LINE_START_LABEL: &str = "Sending the message";
 
 // Prompts
-pub fn prompt(text: &str) -> Result<String> {
-    // let theme = ColorfulTheme::default();
+pub fn confirm(text: &str) -> Result<bool> {
     let theme = ColorfulTheme {
         prompt_style: Style::new().for_stderr().color256(45),
         prompt_prefix: style("?".to_string()).color256(45).for_stderr(),
         ..ColorfulTheme::default()
     };
 
-    let input = Input::with_theme(&theme);
-    let res = input.with_prompt(text).interact_text()?;
+    let res = Confirm::with_theme(&theme)
+        .with_prompt(text)
+        .default(false)
+        .interact()?;
 
     Ok(res)
 }
 
+// Diffs
+// * Prints the changed lines (with some context) from `old` to `new`
+pub fn print_diff(old: &str, new: &str) {
+    let diff = TextDiff::from_lines(old, new);
+
+    for (idx, group) in diff.grouped_ops(3).iter().enumerate() {
+        if idx > 0 {
+            println!("{}", style("...").dim());
+        }
+        for op in group {
+            for change in diff.iter_changes(op) {
+                let line = change.to_string_lossy();
+                let line = line.trim_end_matches('\n');
+                match change.tag() {
+                    ChangeTag::Delete => println!("{}", style(format!("-{line}")).red()),
+                    ChangeTag::Insert => println!("{}", style(format!("+{line}")).green()),
+                    ChangeTag::Equal => println!("{}", style(format!(" {line}")).dim()),
+                }
+            }
+        }
+    }
+}
+
 // Icons
 pub fn icon_check() -> StyledObject<&'static str> {
     style("✔").green()
@@ -39,11 +78,87 @@ pub fn icon_err() -> StyledObject<&'static str> {
     style("✗").red()
 }
 
+pub fn icon_warn() -> StyledObject<&'static str> {
+    style("⚠").yellow()
+}
+
 pub fn icon_res() -> StyledObject<&'static str> {
     style("➤").color256(45)
 }
 
+// Status Line
+// * Returns the spinner frame for the elapsed time
+pub fn spinner_frame(elapsed: Duration) -> &'static str {
+    SPINNER_FRAMES[(elapsed.as_millis() / SPINNER_FRAME_MS) as usize % SPINNER_FRAMES.len()]
+}
+
+// * Awaits the task while showing its live status and elapsed time (on stderr), cleared when done
+// - `status` is the latest status of the task (e.g., `Buddy::run_progress`), `None` until it starts
+// - The console logs clear the status line before being printed (it is redrawn on the next tick)
+pub async fn with_status_line<T, S: Display>(
+    status: watch::Receiver<Option<S>>,
+    task: impl Future<Output = T>,
+) -> T {
+    let term = Term::stderr();
+    if !term.is_term() {
+        return task.await;
+    }
+
+    set_console_printer(Some(Box::new(|msg| {
+        let term = Term::stderr();
+        let _ = term.clear_line();
+        let _ = term.write_str(&msg);
+    })));
+
+    tokio::pin!(task);
+    let started_at = Instant::now();
+    let mut ticker = interval(Duration::from_millis(SPINNER_FRAME_MS as u64));
+    let res = loop {
+        tokio::select! {
+            res = &mut task => break res,
+            _ = ticker.tick() => {
+                let elapsed = started_at.elapsed();
+                let label = status
+                    .borrow()
+                    .as_ref()
+                    .map(|status| status.to_string())
+                    .unwrap_or_else(|| STATUS_LINE_START_LABEL.to_string());
+                // ! NOTE: The status line is only informative, so the terminal errors are ignored
+                let _ = term.clear_line();
+                let _ = term.write_str(&format!(
+                    "{} {} {}",
+                    style(spinner_frame(elapsed)).color256(45),
+                    label,
+                    style(format!("({:.1}s)", elapsed.as_secs_f32())).dim()
+                ));
+            }
+        }
+    };
+    set_console_printer(None);
+    let _ = term.clear_line();
+
+    res
+}
+
 // Text Output
 pub fn text_res(text: String) -> StyledObject<String> {
     style(text).bright()
 }
+
+// * Prints an answer, rendered as Markdown unless `plain`
+pub fn print_res(res: &str, plain: bool) {
+    if plain {
+        println!("{} {}", icon_res(), text_res(res.to_string()));
+    } else {
+        println!("{}\n{}", icon_res(), render_markdown(res, term_width()));
+    }
+}
+
+// Terminal
+// * Returns the current terminal width, or the default width when not a terminal
+pub fn term_width() -> usize {
+    Term::stdout()
+        .size_checked()
+        .map(|(_, cols)| cols as usize)
+        .unwrap_or(DEFAULT_TERM_WIDTH)
+}
@@ -0,0 +1,139 @@
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use console::style;
+use rustyline::{
+    completion::{Completer, FilenameCompleter, Pair},
+    error::ReadlineError,
+    highlight::Highlighter,
+    history::FileHistory,
+    Config, Context, Editor, ExternalPrinter, Helper, Hinter, Validator,
+};
+
+use crate::{logs::set_console_printer, Result};
+
+// Line Editor
+// * Readline style prompt, with persistent history, reverse search (Ctrl-R) and Tab completion
+pub struct LineEditor {
+    editor: Editor<EditorHelper, FileHistory>,
+    history_file: PathBuf,
+    // Prints the logs of the background tasks (e.g., the watch mode) above the prompt
+    // ! NOTE: `None` when the terminal does not support it
+    printer: Option<Arc<Mutex<Box<dyn ExternalPrinter + Send>>>>,
+}
+
+impl LineEditor {
+    // * `cmd_specs` are the `(name, takes_path)` of the slash commands to complete
+    pub fn new(history_file: &Path, cmd_specs: &'static [(&'static str, bool)]) -> Result<Self> {
+        let config = Config::builder().auto_add_history(false).build();
+        let mut editor = Editor::with_config(config)?;
+        editor.set_helper(Some(EditorHelper {
+            cmd_specs,
+            file_completer: FilenameCompleter::new(),
+        }));
+
+        // ! NOTE: The history file does not exist on the first run
+        if history_file.exists() {
+            editor.load_history(history_file)?;
+        }
+
+        let printer = editor.create_external_printer().ok().map(|printer| {
+            Arc::new(Mutex::new(
+                Box::new(printer) as Box<dyn ExternalPrinter + Send>
+            ))
+        });
+
+        Ok(Self {
+            editor,
+            history_file: history_file.to_path_buf(),
+            printer,
+        })
+    }
+
+    // * Returns `None` when the user wants to leave (Ctrl-C or Ctrl-D)
+    pub fn prompt(&mut self, text: &str) -> Result<Option<String>> {
+        if let Some(printer) = self.printer.clone() {
+            set_console_printer(Some(Box::new(move |msg| {
+                if let Ok(mut printer) = printer.lock() {
+                    let _ = printer.print(msg);
+                }
+            })));
+        }
+        let input = self.editor.readline(&format!("? {text} › "));
+        set_console_printer(None);
+
+        let input = match input {
+            Ok(input) => input,
+            Err(ReadlineError::Interrupted | ReadlineError::Eof) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        let input = input.trim().to_string();
+        if !input.is_empty() {
+            self.editor.add_history_entry(&input)?;
+            self.editor.save_history(&self.history_file)?;
+        }
+
+        Ok(Some(input))
+    }
+}
+
+// Editor Helper
+#[derive(Helper, Hinter, Validator)]
+struct EditorHelper {
+    cmd_specs: &'static [(&'static str, bool)],
+    file_completer: FilenameCompleter,
+}
+
+impl Completer for EditorHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        if !line.starts_with('/') {
+            return Ok((0, Vec::new()));
+        }
+
+        // Complete the command name while there is no argument yet
+        let Some((cmd_name, _)) = line[..pos].split_once(' ') else {
+            let candidates = self
+                .cmd_specs
+                .iter()
+                .filter(|(name, _)| name.starts_with(&line[..pos]))
+                .map(|(name, _)| Pair {
+                    display: name.to_string(),
+                    replacement: format!("{name} "),
+                })
+                .collect();
+            return Ok((0, candidates));
+        };
+
+        // Otherwise, complete the file paths for the commands taking paths
+        let takes_path = self
+            .cmd_specs
+            .iter()
+            .any(|(name, takes_path)| *name == cmd_name && *takes_path);
+        if takes_path {
+            self.file_completer.complete(line, pos, ctx)
+        } else {
+            Ok((pos, Vec::new()))
+        }
+    }
+}
+
+impl Highlighter for EditorHelper {
+    fn highlight_prompt<'b, 's: 'b, 'p: 'b>(
+        &'s self,
+        prompt: &'p str,
+        _default: bool,
+    ) -> Cow<'b, str> {
+        Cow::Owned(style(prompt).color256(45).to_string())
+    }
+}
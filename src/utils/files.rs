@@ -1,38 +1,19 @@
 use std::{
     ffi::OsStr,
     fs::{self, File},
-    io::{BufRead, BufReader, BufWriter, Write},
+    io::BufReader,
     path::{Path, PathBuf},
 };
 
-use globset::{Glob, GlobSet, GlobSetBuilder};
-use walkdir::WalkDir;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::{
+    gitignore::{Gitignore, GitignoreBuilder},
+    Match, WalkBuilder,
+};
+use sha2::{Digest, Sha256};
 
 use crate::Result;
 
-// File Bundler
-pub fn bundle_to_file(files: Vec<PathBuf>, dst_file: &Path) -> Result<()> {
-    let mut writer = BufWriter::new(File::create(dst_file)?);
-
-    for file in files {
-        if !file.is_file() {
-            return Err(format!("Cannot Bundle '{:?}' is not a file", file).into());
-        }
-        let reader = BufReader::new(File::open(&file)?);
-
-        writeln!(writer, "\n// ==== File Path: {}\n", file.to_string_lossy())?;
-
-        for line in reader.lines() {
-            let line = line?;
-            writeln!(writer, "{}", line)?;
-        }
-        writeln!(writer, "\n\n")?;
-    }
-    writer.flush()?;
-
-    Ok(())
-}
-
 // File Parser/Writer
 pub fn load_from_toml<T>(file: impl AsRef<Path>) -> Result<T>
 where
@@ -77,67 +58,216 @@ pub fn ensure_dir(dir: &Path) -> Result<bool> {
     }
 }
 
+// * Lists the files of `dir` matching the globs (see `GlobFilter`)
+// - `respect_ignore` skips the files ignored by `.gitignore`, `.ignore` and the global git excludes
 pub fn list_files(
     dir: &Path,
     include_globs: Option<&[&str]>,
     exclude_globs: Option<&[&str]>,
+    respect_ignore: bool,
 ) -> Result<Vec<PathBuf>> {
     let base_dir_exclude = base_dir_exclude_globs()?;
-
-    // Determine Recursive Depth
-    // If there is a ** in the glob, make recursion. If there is * in the glob, is a flat file and do not make recursion
-    let depth = include_globs
-        .map(|globs| globs.iter().any(|&glob| glob.contains("**")))
-        .map(|value| if value { 100 } else { 1 }) // TODO: The value 100 and 1 could be in a Constant
-        .unwrap_or(1);
-
-    // Prepare Globs
-    let include_globs = include_globs.map(get_glob_set).transpose()?;
-    let exclude_globs = exclude_globs.map(get_glob_set).transpose()?;
+    let glob_filter = GlobFilter::new(include_globs, exclude_globs)?;
+    let base_dir = dir.to_path_buf();
 
     // Build File Iterator
-    let walk_dir_iterator = WalkDir::new(dir)
-        .max_depth(depth)
-        .into_iter()
-        .filter_entry(|entry|
-            // if dir, check the dir exclude
-            if entry.file_type().is_dir() {
-                !base_dir_exclude.is_match(entry.path())
+    // ! NOTE: The hidden files are listed (like the other files), only the ignore files are applied
+    let walk_dir_iterator = WalkBuilder::new(dir)
+        .max_depth(glob_filter.max_depth)
+        .hidden(false)
+        .parents(respect_ignore)
+        .ignore(respect_ignore)
+        .git_ignore(respect_ignore)
+        .git_global(respect_ignore)
+        .git_exclude(respect_ignore)
+        // Also apply the `.gitignore` outside of a git repository
+        .require_git(false)
+        .filter_entry(move |entry| {
+            let Ok(rel_path) = entry.path().strip_prefix(&base_dir) else {
+                return false;
+            };
+            // The listed dir itself
+            if rel_path.as_os_str().is_empty() {
+                return true;
+            }
+
+            // if dir, check the dir exclude (and the excluded dirs, e.g., `**/node_modules`)
+            if entry
+                .file_type()
+                .is_some_and(|file_type| file_type.is_dir())
+            {
+                !base_dir_exclude.is_match(rel_path) && !glob_filter.is_excluded(rel_path)
             }
             // else is file, we apply the globs
             else {
-                // Evaluate the exclude
-                if let Some(exclude_globs) = exclude_globs.as_ref() {
-                    if exclude_globs.is_match(entry.path()) {
-                        return false;
-                    }
-                }
-                // Otherwise, evaluate the include
-                match include_globs.as_ref() {
-                    Some(globs) => globs.is_match(entry.path()),
-                    None => true,
-                }
+                glob_filter.is_match(rel_path)
+            }
+        })
+        .build()
+        .filter_map(|entry| {
+            entry.ok().filter(|dir_entry| {
+                dir_entry
+                    .file_type()
+                    .is_some_and(|file_type| file_type.is_file())
+            })
+        });
+
+    // Sorted, so the bundles (and their hashes) do not depend on the dir iteration order
+    let mut paths: Vec<PathBuf> = walk_dir_iterator.map(|entry| entry.into_path()).collect();
+    paths.sort();
+
+    Ok(paths)
+}
+
+// * Returns true if `list_files` would list the file (e.g., to check a changed file without listing `dir`)
+// - `file` must be absolute, like `dir`
+pub fn is_listed_file(
+    dir: &Path,
+    file: &Path,
+    glob_filter: &GlobFilter,
+    respect_ignore: bool,
+) -> Result<bool> {
+    let Ok(rel_path) = file.strip_prefix(dir) else {
+        return Ok(false);
+    };
+    if !glob_filter.is_match(rel_path) {
+        return Ok(false);
+    }
+
+    // The dirs of the file are filtered like in `list_files`
+    let base_dir_exclude = base_dir_exclude_globs()?;
+    let is_dir_excluded = rel_path
+        .ancestors()
+        .skip(1)
+        .filter(|rel_dir| !rel_dir.as_os_str().is_empty())
+        .any(|rel_dir| base_dir_exclude.is_match(rel_dir) || glob_filter.is_excluded(rel_dir));
+    if is_dir_excluded {
+        return Ok(false);
+    }
+
+    Ok(!(respect_ignore && is_ignored(file)))
+}
+
+// * Returns true if the file is ignored by the ignore files of its dirs, or the global git excludes
+// - Same precedence as `list_files`: the nearest dir first, and `.ignore` over `.gitignore`
+fn is_ignored(file: &Path) -> bool {
+    for dir in file.ancestors().skip(1) {
+        let mut builder = GitignoreBuilder::new(dir);
+        for ignore_file in [".git/info/exclude", ".gitignore", ".ignore"] {
+            let ignore_file = dir.join(ignore_file);
+            if ignore_file.is_file() {
+                // ! NOTE: An invalid line is skipped, like in `list_files`
+                let _ = builder.add(ignore_file);
             }
-        )
-        .filter_map(|entry| entry.ok().filter(|dir_entry| dir_entry.file_type().is_file()));
+        }
+        let Ok(gitignore) = builder.build() else {
+            continue;
+        };
 
-    let paths = walk_dir_iterator.map(|entry| entry.into_path());
+        match gitignore.matched_path_or_any_parents(file, false) {
+            Match::Ignore(_) => return true,
+            Match::Whitelist(_) => return false,
+            Match::None => (),
+        }
+    }
 
-    Ok(paths.collect())
+    let (global, _) = Gitignore::global();
+    global.matched(file, false).is_ignore()
 }
 
 fn base_dir_exclude_globs() -> Result<GlobSet> {
     get_glob_set(&["**/.git", "**/target", "**/.env", "**/.env.sample"])
 }
 
+// * Builds the glob set, where `*` does not match the `/` (only `**` matches the nested dirs)
 pub fn get_glob_set(globs: &[&str]) -> Result<GlobSet> {
     let mut builder = GlobSetBuilder::new();
     for glob in globs {
-        builder.add(Glob::new(glob)?);
+        builder.add(GlobBuilder::new(glob).literal_separator(true).build()?);
     }
     Ok(builder.build()?)
 }
 
+// Glob Filter
+// * Include and exclude globs, matched against the paths relative to the listed dir
+// - A `!pattern` in the include globs excludes the matching files (e.g., `["**/*.rs", "!**/tests/**"]`),
+//   with only `!pattern`s all the other files are included (e.g., `["!*.lock"]`)
+// - Without include globs, all the files at the top level of the dir are included
+pub struct GlobFilter {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+    // Depth needed by the include globs (`None` when a `**` can match any depth)
+    max_depth: Option<usize>,
+}
+
+impl GlobFilter {
+    pub fn new(include_globs: Option<&[&str]>, exclude_globs: Option<&[&str]>) -> Result<Self> {
+        let mut includes = Vec::new();
+        let mut excludes: Vec<&str> = exclude_globs.unwrap_or_default().to_vec();
+        for &glob in include_globs.unwrap_or_default() {
+            match glob.strip_prefix('!') {
+                Some(negated) => excludes.push(negated),
+                None => includes.push(glob),
+            }
+        }
+
+        // Only negations (e.g., `["!*.lock"]`) include all the other files
+        if include_globs.is_some() && includes.is_empty() {
+            includes.push("**");
+        }
+
+        // Determine Recursive Depth
+        // A `**` can match any number of dirs, otherwise each `/` is one more level
+        let max_depth = match include_globs {
+            Some(_) if includes.iter().any(|glob| glob.contains("**")) => None,
+            Some(_) => Some(
+                includes
+                    .iter()
+                    .map(|glob| glob_depth(glob))
+                    .max()
+                    .unwrap_or(1),
+            ),
+            None => Some(1),
+        };
+
+        Ok(Self {
+            include: include_globs.map(|_| get_glob_set(&includes)).transpose()?,
+            exclude: get_glob_set(&excludes)?,
+            max_depth,
+        })
+    }
+
+    // * Returns true if the file (relative to the listed dir) is included and not excluded
+    pub fn is_match(&self, rel_path: &Path) -> bool {
+        if self.is_excluded(rel_path) {
+            return false;
+        }
+        if self
+            .max_depth
+            .is_some_and(|max_depth| rel_path.components().count() > max_depth)
+        {
+            return false;
+        }
+
+        match self.include.as_ref() {
+            Some(include) => include.is_match(rel_path),
+            None => true,
+        }
+    }
+
+    pub fn is_excluded(&self, rel_path: &Path) -> bool {
+        self.exclude.is_match(rel_path)
+    }
+}
+
+// * Returns the number of path levels matched by a glob without `**` (e.g., `crates/*/src/lib.rs` is 4)
+fn glob_depth(glob: &str) -> usize {
+    glob.split('/')
+        .filter(|part| !part.is_empty() && *part != ".")
+        .count()
+        .max(1)
+}
+
 // File Utils
 fn get_reader(file: &Path) -> Result<BufReader<File>> {
     let Ok(file) = File::open(file) else {
@@ -156,6 +286,14 @@ pub fn read_to_string(file: &Path) -> Result<String> {
     Ok(content)
 }
 
+// * Returns the SHA-256 of the file content, as hex
+pub fn hash_file(file: &Path) -> Result<String> {
+    let content = fs::read(file)?;
+    let hash = Sha256::digest(&content);
+
+    Ok(hash.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
 // XFile
 // Trait that has methods which return the `&str` when Ok, and When None or Err, return ""
 pub trait XFile {
@@ -172,3 +310,59 @@ impl XFile for Path {
         self.extension().and_then(OsStr::to_str).unwrap_or("")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(include_globs: &[&str]) -> GlobFilter {
+        GlobFilter::new(Some(include_globs), None).unwrap()
+    }
+
+    #[test]
+    fn test_glob_depth() {
+        assert_eq!(glob_depth("*.rs"), 1);
+        assert_eq!(glob_depth("./src/*.rs"), 2);
+        assert_eq!(glob_depth("crates/*/src/lib.rs"), 4);
+    }
+
+    #[test]
+    fn test_glob_filter_max_depth() {
+        assert_eq!(filter(&["*.rs", "src/*.rs"]).max_depth, Some(2));
+        assert_eq!(filter(&["*.rs", "src/**/*.rs"]).max_depth, None);
+        assert_eq!(GlobFilter::new(None, None).unwrap().max_depth, Some(1));
+    }
+
+    #[test]
+    fn test_glob_filter_negation() {
+        let glob_filter = filter(&["**/*.rs", "!**/tests/**"]);
+        assert!(glob_filter.is_match(Path::new("src/main.rs")));
+        assert!(!glob_filter.is_match(Path::new("src/tests/main.rs")));
+        assert!(glob_filter.is_excluded(Path::new("src/tests/main.rs")));
+    }
+
+    #[test]
+    fn test_glob_filter_only_negations() {
+        let glob_filter = filter(&["!*.lock"]);
+        assert_eq!(glob_filter.max_depth, None);
+        assert!(glob_filter.is_match(Path::new("main.rs")));
+        assert!(glob_filter.is_match(Path::new("src/main.rs")));
+        assert!(!glob_filter.is_match(Path::new("Cargo.lock")));
+    }
+
+    #[test]
+    fn test_glob_filter_literal_separator() {
+        let glob_filter = filter(&["src/*.rs"]);
+        assert!(glob_filter.is_match(Path::new("src/main.rs")));
+        assert!(!glob_filter.is_match(Path::new("src/utils/files.rs")));
+    }
+
+    #[test]
+    fn test_glob_filter_nested_crates() {
+        let glob_filter = filter(&["crates/*/src/lib.rs"]);
+        assert_eq!(glob_filter.max_depth, Some(4));
+        assert!(glob_filter.is_match(Path::new("crates/core/src/lib.rs")));
+        assert!(!glob_filter.is_match(Path::new("crates/core/src/main.rs")));
+        assert!(!glob_filter.is_match(Path::new("crates/core/nested/src/lib.rs")));
+    }
+}
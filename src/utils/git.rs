@@ -0,0 +1,65 @@
+use std::{path::Path, time::Duration};
+
+use tokio::{process::Command, time::timeout};
+
+use crate::Result;
+
+// TODO: Define the CONSTANTS in a separate file constants.rs
+const GIT_TIMEOUT_SECS: u64 = 60;
+
+// * Runs git in `dir`, and returns its output
+// - git is killed after `GIT_TIMEOUT_SECS` (e.g., waiting for a credential prompt)
+pub async fn run_git(dir: &Path, args: &[&str]) -> Result<String> {
+    let child = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .kill_on_drop(true)
+        .output();
+
+    let output = timeout(Duration::from_secs(GIT_TIMEOUT_SECS), child)
+        .await
+        .map_err(|_| format!("git {} timed out after {GIT_TIMEOUT_SECS}s", args.join(" ")))?
+        .map_err(|err| format!("Cannot run git: {err}"))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "git {} failed in '{}': {}",
+            args.join(" "),
+            dir.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// * Returns the paths (relative to `dir`) of the files changed since the branch forked from `base`
+// - The committed, uncommitted and untracked changes, without the deleted files
+pub async fn changed_files(dir: &Path, base: &str) -> Result<Vec<String>> {
+    let fork_point = run_git(dir, &["merge-base", base, "HEAD"]).await?;
+    let changed = run_git(
+        dir,
+        &[
+            "diff",
+            "--name-only",
+            "--relative",
+            "--diff-filter=d",
+            fork_point.trim(),
+        ],
+    )
+    .await?;
+    let untracked = run_git(dir, &["ls-files", "--others", "--exclude-standard"]).await?;
+
+    let mut files: Vec<String> = changed
+        .lines()
+        .chain(untracked.lines())
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect();
+    files.sort();
+    files.dedup();
+
+    Ok(files)
+}
@@ -0,0 +1,479 @@
+use std::sync::OnceLock;
+
+use console::{measure_text_width, style, Style};
+use pulldown_cmark::{CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{Theme, ThemeSet},
+    parsing::SyntaxSet,
+    util::as_24_bit_terminal_escaped,
+};
+use textwrap::wrap;
+
+// Constants
+const CODE_THEME: &str = "base16-ocean.dark";
+
+// Markdown Renderer
+// * Renders a Markdown text for the terminal
+// - Prose is wrapped to `width`, fenced code blocks and tables are never wrapped
+pub fn render_markdown(text: &str, width: usize) -> String {
+    let options =
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+
+    let mut renderer = Renderer::new(width);
+    for event in Parser::new_ext(text, options) {
+        renderer.event(event);
+    }
+    renderer.flush_inline();
+
+    renderer.lines.join("\n").trim_end().to_string()
+}
+
+// Code Blocks Extractor
+// * Returns the fenced (and indented) code blocks of a Markdown text, in order
+pub fn extract_code_blocks(text: &str) -> Vec<CodeBlock> {
+    let mut code_blocks = Vec::new();
+    let mut current: Option<CodeBlock> = None;
+
+    for event in Parser::new(text) {
+        match event {
+            Event::Start(Tag::CodeBlock(kind)) => {
+                current = Some(CodeBlock {
+                    lang: code_block_lang(kind),
+                    content: String::new(),
+                });
+            }
+            Event::Text(text) => {
+                if let Some(code_block) = current.as_mut() {
+                    code_block.content.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::CodeBlock) => code_blocks.extend(current.take()),
+            _ => (),
+        }
+    }
+
+    code_blocks
+}
+
+impl CodeBlock {
+    // * Returns the file extension for the language of the block (e.g., `rust` -> `rs`)
+    pub fn extension(&self) -> &str {
+        syntax_set()
+            .find_syntax_by_token(&self.lang)
+            .filter(|_| !self.lang.is_empty())
+            .and_then(|syntax| syntax.file_extensions.first())
+            .map(String::as_str)
+            .unwrap_or("txt")
+    }
+}
+
+fn code_block_lang(kind: CodeBlockKind) -> String {
+    match kind {
+        CodeBlockKind::Fenced(lang) => lang.split(',').next().unwrap_or("").trim().to_string(),
+        CodeBlockKind::Indented => String::new(),
+    }
+}
+
+struct Renderer {
+    width: usize,
+    lines: Vec<String>,
+    // Styled inline text of the current block
+    inline: String,
+    // Inline styles stack (emphasis, strong, code, ...)
+    styles: Vec<Style>,
+    // Block prefixes stack (block quotes and list item indents)
+    prefixes: Vec<String>,
+    // List stack, with the next number for ordered lists
+    lists: Vec<Option<u64>>,
+    // Prefix of the first line of the current list item
+    item_marker: Option<String>,
+    code_block: Option<CodeBlock>,
+    table: Option<Table>,
+    link_dest: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct CodeBlock {
+    pub lang: String,
+    pub content: String,
+}
+
+#[derive(Default)]
+struct Table {
+    rows: Vec<Vec<String>>,
+    head_rows: usize,
+}
+
+impl Renderer {
+    fn new(width: usize) -> Self {
+        Self {
+            width,
+            lines: Vec::new(),
+            inline: String::new(),
+            styles: Vec::new(),
+            prefixes: Vec::new(),
+            lists: Vec::new(),
+            item_marker: None,
+            code_block: None,
+            table: None,
+            link_dest: None,
+        }
+    }
+
+    fn event(&mut self, event: Event) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag_end) => self.end(tag_end),
+            Event::Text(text) => {
+                if let Some(code_block) = self.code_block.as_mut() {
+                    code_block.content.push_str(&text);
+                } else {
+                    self.push_text(&text);
+                }
+            }
+            Event::Code(code) => {
+                let code = style(code.to_string()).color256(180).to_string();
+                self.inline.push_str(&code);
+            }
+            Event::SoftBreak => self.inline.push(' '),
+            Event::HardBreak => self.inline.push('\n'),
+            Event::Rule => {
+                self.flush_inline();
+                let rule = "─".repeat(self.prose_width());
+                self.push_line(style(rule).dim().to_string());
+                self.push_blank_line();
+            }
+            Event::TaskListMarker(checked) => {
+                self.inline.push_str(if checked { "[x] " } else { "[ ] " });
+            }
+            Event::Html(html) | Event::InlineHtml(html) => self.push_text(&html),
+            _ => (),
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Paragraph => self.flush_inline(),
+            Tag::Heading { .. } => self.flush_inline(),
+            Tag::BlockQuote(_) => {
+                self.flush_inline();
+                self.prefixes.push(style("│ ").dim().to_string());
+            }
+            Tag::CodeBlock(kind) => {
+                self.flush_inline();
+                self.code_block = Some(CodeBlock {
+                    lang: code_block_lang(kind),
+                    content: String::new(),
+                });
+            }
+            Tag::List(start) => {
+                self.flush_inline();
+                self.lists.push(start);
+            }
+            Tag::Item => {
+                self.flush_inline();
+                let marker = match self.lists.last_mut() {
+                    Some(Some(num)) => {
+                        let marker = format!("{num}. ");
+                        *num += 1;
+                        marker
+                    }
+                    _ => "• ".to_string(),
+                };
+                self.prefixes.push(" ".repeat(marker.chars().count()));
+                self.item_marker = Some(marker);
+            }
+            Tag::Table(_) => {
+                self.flush_inline();
+                self.table = Some(Table::default());
+            }
+            Tag::TableRow | Tag::TableHead => {
+                if let Some(table) = self.table.as_mut() {
+                    table.rows.push(Vec::new());
+                }
+            }
+            Tag::Emphasis => self.styles.push(Style::new().italic()),
+            Tag::Strong => self.styles.push(Style::new().bold()),
+            Tag::Strikethrough => self.styles.push(Style::new().strikethrough()),
+            Tag::Link { dest_url, .. } => {
+                self.styles.push(Style::new().underlined().color256(45));
+                self.link_dest = Some(dest_url.to_string());
+            }
+            _ => (),
+        }
+    }
+
+    fn end(&mut self, tag_end: TagEnd) {
+        match tag_end {
+            TagEnd::Paragraph => {
+                self.flush_inline();
+                self.push_blank_line();
+            }
+            TagEnd::Heading(level) => {
+                let heading = std::mem::take(&mut self.inline);
+                let heading_style = match level {
+                    HeadingLevel::H1 => Style::new().bold().underlined().color256(45),
+                    HeadingLevel::H2 => Style::new().bold().color256(45),
+                    _ => Style::new().bold(),
+                };
+                let heading = heading_style.apply_to(console::strip_ansi_codes(&heading));
+                self.push_line(heading.to_string());
+                self.push_blank_line();
+            }
+            TagEnd::BlockQuote(_) => {
+                self.flush_inline();
+                self.prefixes.pop();
+                self.push_blank_line();
+            }
+            TagEnd::CodeBlock => {
+                if let Some(code_block) = self.code_block.take() {
+                    let lines = highlight_code(&code_block.content, &code_block.lang);
+                    for (idx, line) in lines.into_iter().enumerate() {
+                        // The marker of an item starting with the code block
+                        let prefix = if idx == 0 {
+                            self.take_first_prefix()
+                        } else {
+                            self.prefixes.concat()
+                        };
+                        self.lines.push(format!("{prefix}{line}"));
+                    }
+                    self.push_blank_line();
+                }
+            }
+            TagEnd::List(_) => {
+                self.flush_inline();
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.push_blank_line();
+                }
+            }
+            TagEnd::Item => {
+                self.flush_inline();
+                // An empty item only has its marker (it must not leak into the next block)
+                if self.item_marker.is_some() {
+                    let marker_line = self.take_first_prefix();
+                    self.lines.push(marker_line.trim_end().to_string());
+                }
+                self.prefixes.pop();
+            }
+            TagEnd::Table => {
+                if let Some(table) = self.table.take() {
+                    for line in table.render() {
+                        self.push_line(line);
+                    }
+                    self.push_blank_line();
+                }
+            }
+            TagEnd::TableHead => {
+                if let Some(table) = self.table.as_mut() {
+                    table.head_rows = table.rows.len();
+                }
+            }
+            TagEnd::TableCell => {
+                let cell = std::mem::take(&mut self.inline);
+                if let Some(row) = self.table.as_mut().and_then(|table| table.rows.last_mut()) {
+                    row.push(cell);
+                }
+            }
+            TagEnd::Emphasis | TagEnd::Strong | TagEnd::Strikethrough => {
+                self.styles.pop();
+            }
+            TagEnd::Link => {
+                self.styles.pop();
+                if let Some(dest_url) = self.link_dest.take() {
+                    let dest_url = style(format!(" ({dest_url})")).dim().to_string();
+                    self.inline.push_str(&dest_url);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn push_text(&mut self, text: &str) {
+        let text = self
+            .styles
+            .iter()
+            .fold(style(text.to_string()), |styled, text_style| {
+                text_style.apply_to(styled.to_string())
+            });
+        self.inline.push_str(&text.to_string());
+    }
+
+    // * Wraps the pending inline text into the lines, with the current block prefixes
+    fn flush_inline(&mut self) {
+        let inline = std::mem::take(&mut self.inline);
+        if inline.trim().is_empty() {
+            // The marker is kept for the next block of the item (e.g., item starting with a code block)
+            return;
+        }
+
+        let prefix = self.prefixes.concat();
+        let first_prefix = self.take_first_prefix();
+
+        let width = self.prose_width();
+        let mut first = true;
+        for paragraph_line in inline.split('\n') {
+            let options = textwrap::Options::new(width)
+                .initial_indent(if first { &first_prefix } else { &prefix })
+                .subsequent_indent(&prefix);
+            for line in wrap(paragraph_line, options) {
+                self.lines.push(line.into_owned());
+            }
+            first = false;
+        }
+    }
+
+    // * Returns the prefix of the next line, with the marker if it is the first line of an item
+    fn take_first_prefix(&mut self) -> String {
+        match self.item_marker.take() {
+            Some(marker) => {
+                // The item indent is replaced by the marker
+                let outer_prefix = self
+                    .prefixes
+                    .split_last()
+                    .map(|(_, outer_prefixes)| outer_prefixes.concat())
+                    .unwrap_or_default();
+                format!("{outer_prefix}{marker}")
+            }
+            None => self.prefixes.concat(),
+        }
+    }
+
+    fn push_line(&mut self, line: String) {
+        let prefix = self.prefixes.concat();
+        self.lines.push(format!("{prefix}{line}"));
+    }
+
+    fn push_blank_line(&mut self) {
+        // Inside lists, items are kept together
+        if !self.lists.is_empty() {
+            return;
+        }
+        if self.lines.last().is_some_and(|line| !line.is_empty()) {
+            self.lines.push(String::new());
+        }
+    }
+
+    fn prose_width(&self) -> usize {
+        self.width.max(20)
+    }
+}
+
+impl Table {
+    fn render(&self) -> Vec<String> {
+        let num_cols = self.rows.iter().map(Vec::len).max().unwrap_or(0);
+        let col_widths: Vec<usize> = (0..num_cols)
+            .map(|col| {
+                self.rows
+                    .iter()
+                    .filter_map(|row| row.get(col))
+                    .map(|cell| measure_text_width(cell))
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        let mut lines = Vec::new();
+        for (idx, row) in self.rows.iter().enumerate() {
+            let cells: Vec<String> = col_widths
+                .iter()
+                .enumerate()
+                .map(|(col, col_width)| {
+                    let cell = row.get(col).map(String::as_str).unwrap_or("");
+                    let padding = " ".repeat(col_width - measure_text_width(cell));
+                    if idx < self.head_rows {
+                        format!("{}{padding}", style(cell).bold())
+                    } else {
+                        format!("{cell}{padding}")
+                    }
+                })
+                .collect();
+            lines.push(format!("│ {} │", cells.join(" │ ")));
+
+            if idx + 1 == self.head_rows {
+                let separators: Vec<String> = col_widths.iter().map(|w| "─".repeat(*w)).collect();
+                lines.push(format!("├─{}─┤", separators.join("─┼─")));
+            }
+        }
+
+        lines
+    }
+}
+
+// Code Highlighting
+fn syntax_set() -> &'static SyntaxSet {
+    static SYNTAX_SET: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAX_SET.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn code_theme() -> &'static Theme {
+    static THEME: OnceLock<Theme> = OnceLock::new();
+    THEME.get_or_init(|| {
+        let mut theme_set = ThemeSet::load_defaults();
+        theme_set.themes.remove(CODE_THEME).unwrap_or_default()
+    })
+}
+
+// * Returns the highlighted lines of the code. When the language is unknown, the lines are only dimmed
+fn highlight_code(code: &str, lang: &str) -> Vec<String> {
+    let syntax_set = syntax_set();
+    let syntax = syntax_set
+        .find_syntax_by_token(lang)
+        .filter(|_| !lang.is_empty());
+
+    let Some(syntax) = syntax else {
+        return code
+            .lines()
+            .map(|line| style(line).color256(250).to_string())
+            .collect();
+    };
+
+    let mut highlighter = HighlightLines::new(syntax, code_theme());
+    code.lines()
+        .map(|line| {
+            // Highlight with the new line, so the syntax states are correct
+            let line = format!("{line}\n");
+            match highlighter.highlight_line(&line, syntax_set) {
+                Ok(ranges) => {
+                    let escaped = as_24_bit_terminal_escaped(&ranges, false);
+                    format!("{}\x1b[0m", escaped.trim_end_matches('\n'))
+                }
+                Err(_) => line.trim_end_matches('\n').to_string(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(text: &str) -> String {
+        console::strip_ansi_codes(&render_markdown(text, 80)).into_owned()
+    }
+
+    #[test]
+    fn test_render_empty_item() {
+        assert_eq!(render("-\n\nHello world"), "•\n\nHello world");
+        assert_eq!(render("- a\n-\n- b"), "• a\n•\n• b");
+    }
+
+    #[test]
+    fn test_render_nested_lists() {
+        assert_eq!(
+            render("1. one\n   - nested\n2. two"),
+            "1. one\n   • nested\n2. two"
+        );
+    }
+
+    #[test]
+    fn test_render_item_with_code_block() {
+        assert_eq!(render("- ```\n  code\n  ```\n- b"), "• code\n• b");
+    }
+
+    #[test]
+    fn test_render_block_quote_with_list() {
+        assert_eq!(render("> - a\n> - b\n\nafter"), "│ • a\n│ • b\n\nafter");
+    }
+}
@@ -1,2 +1,6 @@
+pub mod bundle;
 pub mod cli;
+pub mod editor;
 pub mod files;
+pub mod git;
+pub mod markdown;
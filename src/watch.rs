@@ -0,0 +1,139 @@
+use std::{collections::HashSet, path::PathBuf, time::Duration};
+
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::{
+    sync::mpsc,
+    time::{timeout_at, Instant},
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    buddy::{Buddy, WatchChanges},
+    utils::cli::{icon_check, icon_uploaded},
+    Result,
+};
+
+// TODO: Define the CONSTANTS in a separate file constants.rs
+// Quiet period after the last change, before syncing (e.g., while an editor saves many files)
+const WATCH_QUIET_MS: u64 = 1500;
+// Max wait after the first change, so the changes are synced even if the files keep changing
+const WATCH_MAX_WAIT_MS: u64 = 10_000;
+
+// Watch Mode
+// * Watches the bundle sources, the instructions and `buddy.toml`, and re-syncs only what changed,
+//   after a quiet period. Runs until the task is dropped (or the watcher fails)
+pub async fn watch_and_sync(mut buddy: Buddy) -> Result<()> {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+        if let Ok(event) = res {
+            if !event.kind.is_access() {
+                for path in event.paths {
+                    let _ = tx.send(path);
+                }
+            }
+        }
+    })?;
+    let mut watched = watch_paths(&mut watcher, &buddy)?;
+    info!(
+        "{} Watching Buddy {} for Changes",
+        icon_check(),
+        buddy.name()
+    );
+
+    while let Some(path) = rx.recv().await {
+        // Collect the changes until the quiet period (or the max wait)
+        let mut changed = HashSet::from([path]);
+        let max_deadline = Instant::now() + Duration::from_millis(WATCH_MAX_WAIT_MS);
+        loop {
+            let deadline =
+                (Instant::now() + Duration::from_millis(WATCH_QUIET_MS)).min(max_deadline);
+            match timeout_at(deadline, rx.recv()).await {
+                Ok(Some(path)) => changed.insert(path),
+                _ => break,
+            };
+            if Instant::now() >= max_deadline {
+                break;
+            }
+        }
+
+        let changes = match buddy.watch_changes(&changed) {
+            Ok(changes) if !changes.is_empty() => changes,
+            Ok(_) => continue,
+            Err(err) => {
+                error!("Cannot check the changes - {err}");
+                continue;
+            }
+        };
+
+        match sync_changes(&mut buddy, &changes).await {
+            Ok(synced) => info!("{} Synced - {}", icon_uploaded(), synced),
+            Err(err) => error!("Cannot sync the changes - {err}"),
+        }
+
+        // The bundles (and so the paths to watch) might have changed
+        if changes.config {
+            for path in watched.drain(..) {
+                let _ = watcher.unwatch(&path);
+            }
+            watched = watch_paths(&mut watcher, &buddy)?;
+        }
+    }
+
+    Ok(())
+}
+
+// * Uploads what the changes affect, and returns what was synced
+async fn sync_changes(buddy: &mut Buddy, changes: &WatchChanges) -> Result<String> {
+    if changes.config {
+        let dir = buddy.dir().to_path_buf();
+        *buddy = Buddy::load_from_dir(&dir, false).await?;
+        buddy.upload_instructions().await?;
+        let num_uploaded = buddy.upload_files(false).await?;
+        return Ok(format!(
+            "buddy.toml changed, instructions and {num_uploaded} bundles uploaded \
+             (restart the chat to use the new config)"
+        ));
+    }
+
+    let mut synced = Vec::new();
+    if changes.instructions {
+        buddy.upload_instructions().await?;
+        synced.push("instructions".to_string());
+    }
+    if !changes.bundles.is_empty() {
+        let num_uploaded = buddy.upload_bundles(false, Some(&changes.bundles)).await?;
+        synced.push(format!(
+            "bundles {} ({num_uploaded} uploaded)",
+            changes.bundles.join(", ")
+        ));
+    }
+
+    Ok(synced.join(", "))
+}
+
+// * Watches the paths of the buddy, and returns them (to unwatch them later)
+// - The paths are canonicalized, so the changed paths can be compared to the buddy files
+fn watch_paths(watcher: &mut RecommendedWatcher, buddy: &Buddy) -> Result<Vec<PathBuf>> {
+    let mut watched = Vec::new();
+    let mut seen = HashSet::new();
+
+    for (path, recursive) in buddy.watched_paths() {
+        let Ok(path) = path.canonicalize() else {
+            warn!("Cannot watch '{}', it does not exist", path.display());
+            continue;
+        };
+        if !seen.insert((path.clone(), recursive)) {
+            continue;
+        }
+
+        let mode = if recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        watcher.watch(&path, mode)?;
+        watched.push(path);
+    }
+
+    Ok(watched)
+}